@@ -0,0 +1,252 @@
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::ptr::{self, NonNull};
+
+use crate::bytes::ByteArena;
+
+/// An arena for values of any type, made up of a linked list of chunks with `N` bytes each.
+///
+/// The values are bump-allocated in a [`ByteArena`]. Values that need to be dropped are
+/// stored behind a small header with their drop glue, and dropping the arena drops them in
+/// reverse allocation order. Because of that, such values may only borrow data that
+/// outlives the arena, which is what the lifetime `'a` stands for:
+///
+/// ```compile_fail
+/// use linked_list_arena::any::AnyArena;
+///
+/// let arena = AnyArena::<64>::new();
+/// {
+///     let s = String::from("short-lived");
+///     arena.alloc(vec![s.as_str()]);
+/// }
+/// ```
+///
+/// `Copy` values are never dropped, so [`AnyArena::alloc_copy`] doesn't restrict their
+/// lifetimes, and they may refer to each other.
+pub struct AnyArena<'a, const N: usize> {
+    bytes: ByteArena<N>,
+    /// The header of the newest value that needs to be dropped.
+    last_drop: Cell<Option<NonNull<DropHeader>>>,
+    /// The arena drops values that borrow data for `'a`, and `Cell` makes it invariant in
+    /// `'a`.
+    _marker: PhantomData<Cell<&'a ()>>,
+}
+
+/// The header in front of every value that needs to be dropped.
+///
+/// The headers form a linked list from the newest to the oldest value.
+struct DropHeader {
+    prev: Option<NonNull<DropHeader>>,
+    drop_value: unsafe fn(NonNull<DropHeader>),
+}
+
+/// A value together with its header.
+#[repr(C)]
+struct WithHeader<T> {
+    header: DropHeader,
+    value: T,
+}
+
+impl<'a, const N: usize> AnyArena<'a, N> {
+    /// Creates a new arena.
+    /// This function does not allocate any memory.
+    pub fn new() -> Self {
+        AnyArena {
+            bytes: ByteArena::new(),
+            last_drop: Cell::new(None),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Allocates a value of any type in the arena and returns a mutable reference to it.
+    ///
+    /// The value is dropped together with the arena. If `T` doesn't need to be dropped, no
+    /// header is stored for it.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T: 'a>(&self, value: T) -> &mut T {
+        if !core::mem::needs_drop::<T>() {
+            return self.alloc_without_header(value);
+        }
+        let ptr = self
+            .bytes
+            .alloc_layout(Layout::new::<WithHeader<T>>())
+            .cast::<WithHeader<T>>();
+        unsafe {
+            ptr.write(WithHeader {
+                header: DropHeader {
+                    prev: self.last_drop.get(),
+                    drop_value: drop_value::<T>,
+                },
+                value,
+            });
+            self.last_drop.set(Some(ptr.cast()));
+            // The header is only accessed through raw pointers, so it doesn't overlap with
+            // the returned reference.
+            &mut *ptr::addr_of_mut!((*ptr.as_ptr()).value)
+        }
+    }
+
+    /// Allocates a `Copy` value in the arena and returns a mutable reference to it.
+    ///
+    /// Unlike with [`AnyArena::alloc`], the value may borrow data that doesn't outlive the
+    /// arena, e.g. other values in the same arena.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_copy<T: Copy>(&self, value: T) -> &mut T {
+        self.alloc_without_header(value)
+    }
+
+    /// Copies the slice into the arena and returns the copy.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_copy<T: Copy>(&self, src: &[T]) -> &mut [T] {
+        self.bytes.alloc_slice_copy(src)
+    }
+
+    /// Copies the string into the arena and returns the copy.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_str(&self, src: &str) -> &mut str {
+        self.bytes.alloc_str(src)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Allocates a value that is never dropped.
+    #[allow(clippy::mut_from_ref)]
+    fn alloc_without_header<T>(&self, value: T) -> &mut T {
+        let mut ptr = self.bytes.alloc_layout(Layout::new::<T>()).cast::<T>();
+        unsafe {
+            ptr.write(value);
+            ptr.as_mut()
+        }
+    }
+}
+
+impl<const N: usize> Default for AnyArena<'_, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Drops the value behind the header.
+///
+/// # Safety
+///
+/// The header must be part of an initialized `WithHeader<T>`, whose value is not used
+/// afterwards.
+unsafe fn drop_value<T>(header: NonNull<DropHeader>) {
+    ptr::drop_in_place(ptr::addr_of_mut!(
+        (*header.cast::<WithHeader<T>>().as_ptr()).value
+    ));
+}
+
+impl<const N: usize> Drop for AnyArena<'_, N> {
+    fn drop(&mut self) {
+        while let Some(header) = self.last_drop.get() {
+            unsafe {
+                let DropHeader { prev, drop_value } = header.read();
+                // Unlink the value first, so that a panicking destructor can't lead to it
+                // being dropped again.
+                self.last_drop.set(prev);
+                drop_value(header);
+            }
+        }
+        // The memory itself is freed by the byte arena.
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::mem::align_of;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn alloc_mixed_types() {
+        let arena = AnyArena::<32>::new();
+        assert!(arena.is_empty());
+        let number = arena.alloc(1u8);
+        let string = arena.alloc(String::from("hello"));
+        let numbers = arena.alloc(vec![1u64, 2, 3]);
+        let name = arena.alloc_str("world");
+        let big = arena.alloc([7u32; 20]);
+        *number += 1;
+        string.push('!');
+        numbers.push(4);
+        assert_eq!(*number, 2);
+        assert_eq!(string, "hello!");
+        assert_eq!(numbers, &[1, 2, 3, 4]);
+        assert_eq!(name, "world");
+        assert_eq!(big, &[7; 20]);
+        assert_eq!(
+            numbers as *const Vec<u64> as usize % align_of::<Vec<u64>>(),
+            0
+        );
+    }
+
+    #[test]
+    fn drop_in_reverse_order() {
+        struct Noisy(i32, Rc<RefCell<Vec<i32>>>);
+
+        impl Drop for Noisy {
+            fn drop(&mut self) {
+                self.1.borrow_mut().push(self.0);
+            }
+        }
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let arena = AnyArena::<16>::new();
+        for i in 0..5 {
+            arena.alloc(Noisy(i, Rc::clone(&log)));
+            arena.alloc(i.to_string());
+        }
+        drop(arena);
+        assert_eq!(*log.borrow(), [4, 3, 2, 1, 0]);
+        assert_eq!(Rc::strong_count(&log), 1);
+    }
+
+    #[test]
+    fn drop_zero_sized_values() {
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        struct Zst;
+
+        impl Drop for Zst {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let arena = AnyArena::<16>::new();
+        for _ in 0..3 {
+            arena.alloc(Zst);
+        }
+        drop(arena);
+        assert_eq!(DROPS.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn copy_values_refer_to_each_other() {
+        #[derive(Clone, Copy)]
+        struct Node<'a> {
+            value: i32,
+            next: Option<&'a Node<'a>>,
+        }
+
+        let arena = AnyArena::<64>::new();
+        let mut list = None;
+        for value in 0..10 {
+            list = Some(&*arena.alloc_copy(Node { value, next: list }));
+        }
+        let mut values = Vec::new();
+        while let Some(node) = list {
+            values.push(node.value);
+            list = node.next;
+        }
+        assert!(values.into_iter().eq((0..10).rev()));
+    }
+}
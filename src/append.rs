@@ -0,0 +1,280 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+/// The writing half of an append-only arena that can be read from other threads while it
+/// grows.
+///
+/// There is exactly one writer. Readers are created with [`AppendWriter::reader`] and can
+/// be sent to other threads. An element becomes visible to readers once [`push`] has
+/// returned, and it is never modified or moved afterwards.
+///
+/// [`push`]: AppendWriter::push
+pub struct AppendWriter<const N: usize, T> {
+    shared: Arc<Shared<N, T>>,
+    /// The newest chunk, or null if nothing has been pushed yet.
+    tail: *mut Chunk<N, T>,
+}
+
+/// A reading handle for an append-only arena, created by [`AppendWriter::reader`].
+pub struct AppendReader<const N: usize, T> {
+    shared: Arc<Shared<N, T>>,
+}
+
+struct Shared<const N: usize, T> {
+    /// The oldest chunk, or null if nothing has been pushed yet.
+    first: AtomicPtr<Chunk<N, T>>,
+    /// The number of published elements in all chunks together.
+    len: AtomicUsize,
+}
+
+struct Chunk<const N: usize, T> {
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    /// The number of published elements in this chunk.
+    ///
+    /// Stored with `Release` after the element has been written, so a reader that loads it
+    /// with `Acquire` may read all slots below it.
+    len: AtomicUsize,
+    /// The next (newer) chunk. Only set once this chunk is full.
+    next: AtomicPtr<Chunk<N, T>>,
+}
+
+// The writer moves `T`s in and readers on other threads get `&T`s out.
+unsafe impl<const N: usize, T: Send + Sync> Send for Shared<N, T> {}
+unsafe impl<const N: usize, T: Send + Sync> Sync for Shared<N, T> {}
+unsafe impl<const N: usize, T: Send + Sync> Send for AppendWriter<N, T> {}
+
+impl<const N: usize, T> Chunk<N, T> {
+    /// Allocates a chunk directly on the heap, leaving the slots uninitialized.
+    fn new() -> *mut Self {
+        let chunk = Box::into_raw(Box::<Self>::new_uninit()).cast::<Self>();
+        unsafe {
+            ptr::addr_of_mut!((*chunk).len).write(AtomicUsize::new(0));
+            ptr::addr_of_mut!((*chunk).next).write(AtomicPtr::new(ptr::null_mut()));
+        }
+        chunk
+    }
+
+    /// Returns the chunk together with the number of elements that may be read from it.
+    fn snapshot(&self) -> (&Self, usize) {
+        (self, self.len.load(Ordering::Acquire))
+    }
+}
+
+impl<const N: usize, T> AppendWriter<N, T> {
+    /// Creates a new, empty arena.
+    /// This function does not allocate any chunks.
+    pub fn new() -> Self {
+        const { crate::check_chunk_size::<N, T>() };
+        AppendWriter {
+            shared: Arc::new(Shared {
+                first: AtomicPtr::new(ptr::null_mut()),
+                len: AtomicUsize::new(0),
+            }),
+            tail: ptr::null_mut(),
+        }
+    }
+
+    /// Creates a new reader for this arena.
+    pub fn reader(&self) -> AppendReader<N, T> {
+        AppendReader {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Appends an element and publishes it to all readers.
+    pub fn push(&mut self, elem: T) {
+        // Only the writer modifies the chunk lengths, so relaxed loads are enough here.
+        if self.tail.is_null() {
+            self.tail = Chunk::new();
+            self.shared.first.store(self.tail, Ordering::Release);
+        } else if unsafe { (*self.tail).len.load(Ordering::Relaxed) } == N {
+            let new_tail = Chunk::new();
+            unsafe { (*self.tail).next.store(new_tail, Ordering::Release) };
+            self.tail = new_tail;
+        }
+        let tail = unsafe { &*self.tail };
+        let index = tail.len.load(Ordering::Relaxed);
+        // Readers never look at slots at or above `len`.
+        unsafe { (*tail.slots[index].get()).write(elem) };
+        tail.len.store(index + 1, Ordering::Release);
+        self.shared.len.fetch_add(1, Ordering::Release);
+    }
+
+    /// Returns the number of elements in the arena.
+    pub fn len(&self) -> usize {
+        self.shared.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<const N: usize, T> Default for AppendWriter<N, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, T> AppendReader<N, T> {
+    /// Returns the number of elements that have been published so far.
+    pub fn len(&self) -> usize {
+        self.shared.len.load(Ordering::Acquire)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over the published elements, in allocation order.
+    ///
+    /// The iterator yields at least all elements that were published when it was created.
+    /// Elements published while iterating may or may not be yielded, but the iterator
+    /// always yields a prefix of the arena without gaps.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        let first = unsafe { self.shared.first.load(Ordering::Acquire).as_ref() };
+        core::iter::successors(first.map(Chunk::snapshot), move |&(chunk, len)| {
+            // Only a full chunk can have a successor. If our snapshot of this chunk is not
+            // full, following `next` could skip the elements that were added after it.
+            if len < N {
+                return None;
+            }
+            unsafe { chunk.next.load(Ordering::Acquire).as_ref() }.map(Chunk::snapshot)
+        })
+        .flat_map(|(chunk, len)| {
+            chunk.slots[..len]
+                .iter()
+                .map(|slot| unsafe { (*slot.get()).assume_init_ref() })
+        })
+    }
+
+    /// Returns a reference to the element with the given index, where the index counts
+    /// the elements in allocation order.
+    ///
+    /// This walks `index / N` chunks.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.iter().nth(index)
+    }
+}
+
+impl<const N: usize, T> Clone for AppendReader<N, T> {
+    fn clone(&self) -> Self {
+        AppendReader {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<const N: usize, T> Drop for Shared<N, T> {
+    fn drop(&mut self) {
+        let mut chunk = *self.first.get_mut();
+        while !chunk.is_null() {
+            let mut boxed = unsafe { Box::from_raw(chunk) };
+            let len = *boxed.len.get_mut();
+            for slot in &mut boxed.slots[..len] {
+                unsafe { slot.get_mut().assume_init_drop() };
+            }
+            chunk = *boxed.next.get_mut();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn large_chunks() {
+        // A chunk is larger than the stack of a test thread.
+        let mut writer = AppendWriter::<{ 1 << 20 }, u64>::new();
+        writer.push(1);
+        assert_eq!(writer.reader().iter().next(), Some(&1));
+    }
+
+    #[test]
+    fn push_and_read() {
+        let mut writer = AppendWriter::<3, String>::new();
+        let reader = writer.reader();
+        assert!(reader.is_empty());
+        assert_eq!(reader.iter().next(), None);
+        for i in 0..7 {
+            writer.push(i.to_string());
+        }
+        assert_eq!(writer.len(), 7);
+        assert_eq!(reader.len(), 7);
+        assert!(reader
+            .iter()
+            .map(String::as_str)
+            .eq(["0", "1", "2", "3", "4", "5", "6"]));
+        assert_eq!(reader.get(4).map(String::as_str), Some("4"));
+        assert_eq!(reader.get(7), None);
+    }
+
+    #[test]
+    fn readers_outlive_writer() {
+        let mut writer = AppendWriter::<2, i32>::new();
+        let reader = writer.reader();
+        writer.push(1);
+        writer.push(2);
+        writer.push(3);
+        drop(writer);
+        let other = reader.clone();
+        drop(reader);
+        assert!(other.iter().copied().eq([1, 2, 3]));
+    }
+
+    #[test]
+    fn zero_sized_types() {
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        struct Zst;
+
+        impl Drop for Zst {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let mut writer = AppendWriter::<2, Zst>::new();
+        let reader = writer.reader();
+        for _ in 0..5 {
+            writer.push(Zst);
+        }
+        assert_eq!(reader.len(), 5);
+        assert_eq!(reader.iter().count(), 5);
+        drop(writer);
+        drop(reader);
+        assert_eq!(DROPS.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn read_while_writing() {
+        const COUNT: usize = if cfg!(miri) { 100 } else { 10_000 };
+
+        let mut writer = AppendWriter::<16, usize>::new();
+        let reader = writer.reader();
+        std::thread::scope(|s| {
+            for _ in 0..3 {
+                let reader = reader.clone();
+                s.spawn(move || loop {
+                    let len = reader.len();
+                    // We see at least every element published before we started, without
+                    // gaps and in order.
+                    let seen = reader.iter().enumerate().all(|(i, &x)| i == x);
+                    assert!(seen);
+                    assert!(reader.iter().count() >= len);
+                    if len == COUNT {
+                        break;
+                    }
+                });
+            }
+            for i in 0..COUNT {
+                writer.push(i);
+            }
+        });
+    }
+}
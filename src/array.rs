@@ -0,0 +1,266 @@
+use alloc::boxed::Box;
+use core::cell::Cell;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+type ChunkPtr<const N: usize, T> = NonNull<[MaybeUninit<T>; N]>;
+
+/// An arena with at most `M` chunks of `N` elements each.
+///
+/// Instead of a linked list, the chunks are stored in an inline array. This means that the
+/// address of an element can be computed from its index in O(1), and that the arena can
+/// never hold more than `N * M` elements.
+///
+/// Like [`Arena`](crate::Arena), `ArrayArena` is invariant in `T`:
+///
+/// ```compile_fail
+/// use linked_list_arena::array::ArrayArena;
+///
+/// fn shorten<'a>(arena: &'a ArrayArena<4, 2, &'static str>) -> &'a ArrayArena<4, 2, &'a str> {
+///     arena
+/// }
+/// ```
+pub struct ArrayArena<const N: usize, const M: usize, T> {
+    /// The chunks that have been allocated so far, in allocation order.
+    chunks: [Cell<Option<ChunkPtr<N, T>>>; M],
+    /// The number of initialized elements.
+    len: Cell<usize>,
+}
+
+impl<const N: usize, const M: usize, T> ArrayArena<N, M, T> {
+    /// Creates a new arena.
+    /// This function does not allocate any memory.
+    pub fn new() -> Self {
+        const { crate::check_chunk_size::<N, T>() };
+        ArrayArena {
+            chunks: [const { Cell::new(None) }; M],
+            len: Cell::new(0),
+        }
+    }
+
+    /// Creates a new arena and allocates all `M` chunks up front.
+    ///
+    /// Afterwards, [`ArrayArena::try_alloc`] never allocates memory, takes no locks and
+    /// finishes in a bounded number of steps, which makes it usable on realtime threads.
+    /// Running out of space is reported by returning the element.
+    pub fn preallocated() -> Self {
+        let arena = Self::new();
+        for chunk in &arena.chunks {
+            chunk.set(Some(Self::new_chunk()));
+        }
+        arena
+    }
+
+    fn new_chunk() -> ChunkPtr<N, T> {
+        let ptr = Box::into_raw(crate::new_uninit_slots());
+        unsafe { NonNull::new_unchecked(ptr) }
+    }
+
+    /// Allocates a new element in the arena and returns a mutable reference to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the arena already holds `N * M` elements.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc(&self, elem: T) -> &mut T {
+        match self.try_alloc(elem) {
+            Ok(slot) => slot,
+            Err(_) => panic!("ArrayArena is full"),
+        }
+    }
+
+    /// Allocates a new element in the arena and returns a mutable reference to it.
+    ///
+    /// If the arena already holds `N * M` elements, the element is given back.
+    #[allow(clippy::mut_from_ref)]
+    pub fn try_alloc(&self, elem: T) -> Result<&mut T, T> {
+        let index = self.len.get();
+        let Some(chunk) = self.chunks.get(index / N) else {
+            return Err(elem);
+        };
+        let ptr = match chunk.get() {
+            Some(ptr) => ptr,
+            None => {
+                let ptr = Self::new_chunk();
+                chunk.set(Some(ptr));
+                ptr
+            }
+        };
+        self.len.set(index + 1);
+        let slot = unsafe { ptr.cast::<MaybeUninit<T>>().add(index % N).as_mut() };
+        Ok(slot.write(elem))
+    }
+
+    /// Returns the number of elements in the arena.
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+
+    /// Returns the maximum number of elements the arena can hold.
+    pub const fn capacity(&self) -> usize {
+        N * M
+    }
+
+    /// Returns a raw pointer to the element with the given index, where the index counts
+    /// the elements in allocation order.
+    ///
+    /// The pointer is valid until the arena is dropped.
+    pub fn get_ptr(&self, index: usize) -> Option<NonNull<T>> {
+        if index >= self.len.get() {
+            return None;
+        }
+        let chunk = self.chunks[index / N].get()?;
+        Some(unsafe { chunk.cast::<T>().add(index % N) })
+    }
+
+    /// Returns a mutable reference to the element with the given index, where the index
+    /// counts the elements in allocation order.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        // We have unique access to the arena, so no other reference to the element exists.
+        self.get_ptr(index).map(|mut ptr| unsafe { ptr.as_mut() })
+    }
+}
+
+impl<const N: usize, const M: usize, T> Default for ArrayArena<N, M, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, const M: usize, T> Drop for ArrayArena<N, M, T> {
+    fn drop(&mut self) {
+        let mut remaining = self.len.get();
+        for chunk in &self.chunks {
+            let Some(ptr) = chunk.get() else {
+                break;
+            };
+            let len = remaining.min(N);
+            remaining -= len;
+            unsafe {
+                let elems = core::ptr::slice_from_raw_parts_mut(ptr.cast::<T>().as_ptr(), len);
+                core::ptr::drop_in_place(elems);
+                drop(Box::from_raw(ptr.as_ptr()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn empty_arena() {
+        let mut arena = ArrayArena::<4, 2, i32>::new();
+        assert!(arena.is_empty());
+        assert_eq!(arena.capacity(), 8);
+        assert_eq!(arena.get_mut(0), None);
+    }
+
+    #[test]
+    fn fill_arena() {
+        let mut arena = ArrayArena::<3, 2, i32>::new();
+        let el1 = arena.alloc(0);
+        for i in 1..6 {
+            arena.alloc(i);
+        }
+        assert_eq!(*el1, 0);
+        assert_eq!(arena.len(), 6);
+        assert_eq!(arena.try_alloc(6), Err(6));
+
+        assert_eq!(arena.get_mut(4), Some(&mut 4));
+        *arena.get_mut(5).unwrap() = 50;
+        assert_eq!(unsafe { *arena.get_ptr(5).unwrap().as_ptr() }, 50);
+        assert_eq!(arena.get_ptr(6), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn alloc_when_full() {
+        let arena = ArrayArena::<2, 1, i32>::new();
+        arena.alloc(1);
+        arena.alloc(2);
+        arena.alloc(3);
+    }
+
+    #[test]
+    fn zero_chunks() {
+        let arena = ArrayArena::<2, 0, i32>::new();
+        assert_eq!(arena.capacity(), 0);
+        assert_eq!(arena.try_alloc(1), Err(1));
+    }
+
+    #[test]
+    fn preallocated() {
+        let mut arena = ArrayArena::<3, 2, i32>::preallocated();
+        assert!(arena.is_empty());
+        assert!(arena.chunks.iter().all(|chunk| chunk.get().is_some()));
+        for i in 0..6 {
+            assert!(arena.try_alloc(i).is_ok());
+        }
+        assert_eq!(arena.try_alloc(6), Err(6));
+        assert_eq!(arena.get_mut(5), Some(&mut 5));
+    }
+
+    #[test]
+    fn drop_partially_used_preallocated() {
+        let drop_counter = Arc::new(AtomicUsize::new(0));
+
+        let arena = ArrayArena::<3, 4, WithDrop>::preallocated();
+        arena.alloc(WithDrop(Arc::clone(&drop_counter)));
+        drop(arena);
+
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn zero_sized_types() {
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        struct Zst;
+
+        impl Drop for Zst {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let mut arena = ArrayArena::<3, 2, Zst>::new();
+        for _ in 0..6 {
+            arena.alloc(Zst);
+        }
+        assert!(arena.try_alloc(Zst).is_err());
+        assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+        assert!(arena.get_mut(5).is_some());
+        drop(arena);
+        assert_eq!(DROPS.load(Ordering::SeqCst), 7);
+    }
+
+    struct WithDrop(Arc<AtomicUsize>);
+
+    impl Drop for WithDrop {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn drop_arena() {
+        let drop_counter = Arc::new(AtomicUsize::new(0));
+
+        let arena = ArrayArena::<3, 4, WithDrop>::new();
+        for _ in 0..7 {
+            arena.alloc(WithDrop(Arc::clone(&drop_counter)));
+        }
+        drop(arena);
+
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 7);
+    }
+}
@@ -0,0 +1,230 @@
+use core::cell::Cell;
+use core::fmt;
+use core::ops::Deref;
+
+use crate::Arena;
+
+type NodeLink<'a, T> = Cell<Option<&'a Node<'a, T>>>;
+
+/// A tree node that is meant to be allocated in an [`Arena`].
+///
+/// The links to parent, children and siblings are stored in `Cell`s, so that the tree can
+/// be modified through shared references. Use [`NodeRef`] to work with nodes.
+pub struct Node<'a, T> {
+    data: T,
+    parent: NodeLink<'a, T>,
+    first_child: NodeLink<'a, T>,
+    last_child: NodeLink<'a, T>,
+    prev_sibling: NodeLink<'a, T>,
+    next_sibling: NodeLink<'a, T>,
+}
+
+/// A copyable reference to a [`Node`] in an arena.
+///
+/// Dereferences to the data of the node.
+pub struct NodeRef<'a, T>(&'a Node<'a, T>);
+
+impl<'a, T> NodeRef<'a, T> {
+    /// Allocates a new node without parent, children or siblings in the arena.
+    pub fn new<const N: usize>(arena: &'a Arena<N, Node<'a, T>>, data: T) -> Self {
+        NodeRef(arena.alloc(Node {
+            data,
+            parent: Cell::new(None),
+            first_child: Cell::new(None),
+            last_child: Cell::new(None),
+            prev_sibling: Cell::new(None),
+            next_sibling: Cell::new(None),
+        }))
+    }
+
+    /// Returns the data stored in the node with the lifetime of the arena.
+    pub fn data(self) -> &'a T {
+        &self.0.data
+    }
+
+    pub fn parent(self) -> Option<Self> {
+        self.0.parent.get().map(NodeRef)
+    }
+
+    pub fn first_child(self) -> Option<Self> {
+        self.0.first_child.get().map(NodeRef)
+    }
+
+    pub fn last_child(self) -> Option<Self> {
+        self.0.last_child.get().map(NodeRef)
+    }
+
+    pub fn prev_sibling(self) -> Option<Self> {
+        self.0.prev_sibling.get().map(NodeRef)
+    }
+
+    pub fn next_sibling(self) -> Option<Self> {
+        self.0.next_sibling.get().map(NodeRef)
+    }
+
+    /// Returns an iterator over the children of the node, from first to last.
+    pub fn children(self) -> Children<'a, T> {
+        Children {
+            next: self.first_child(),
+        }
+    }
+
+    /// Returns an iterator over the ancestors of the node, starting with its parent.
+    pub fn ancestors(self) -> impl Iterator<Item = Self> {
+        core::iter::successors(self.parent(), |node| node.parent())
+    }
+
+    /// Returns whether both references point to the same node.
+    pub fn ptr_eq(self, other: Self) -> bool {
+        core::ptr::eq(self.0, other.0)
+    }
+
+    /// Appends `child` as the last child of this node.
+    ///
+    /// If `child` is already part of a tree, it is detached from it first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `child` is this node or one of its ancestors, because that would create a
+    /// cycle.
+    pub fn append_child(self, child: Self) {
+        assert!(
+            !self.ptr_eq(child) && !self.ancestors().any(|a| a.ptr_eq(child)),
+            "cannot append a node to itself or to one of its descendants"
+        );
+        child.detach();
+        child.0.parent.set(Some(self.0));
+        if let Some(last) = self.0.last_child.replace(Some(child.0)) {
+            last.next_sibling.set(Some(child.0));
+            child.0.prev_sibling.set(Some(last));
+        } else {
+            self.0.first_child.set(Some(child.0));
+        }
+    }
+
+    /// Removes the node from its parent and siblings.
+    ///
+    /// The children of the node stay attached to it.
+    pub fn detach(self) {
+        let parent = self.0.parent.take();
+        let prev = self.0.prev_sibling.take();
+        let next = self.0.next_sibling.take();
+        match prev {
+            Some(prev) => prev.next_sibling.set(next),
+            None => {
+                if let Some(parent) = parent {
+                    parent.first_child.set(next);
+                }
+            }
+        }
+        match next {
+            Some(next) => next.prev_sibling.set(prev),
+            None => {
+                if let Some(parent) = parent {
+                    parent.last_child.set(prev);
+                }
+            }
+        }
+    }
+}
+
+impl<T> Clone for NodeRef<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for NodeRef<'_, T> {}
+
+impl<T> Deref for NodeRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0.data
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for NodeRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("NodeRef").field(&self.0.data).finish()
+    }
+}
+
+/// An iterator over the children of a node, created by [`NodeRef::children`].
+pub struct Children<'a, T> {
+    next: Option<NodeRef<'a, T>>,
+}
+
+impl<'a, T> Iterator for Children<'a, T> {
+    type Item = NodeRef<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next?;
+        self.next = node.next_sibling();
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn data<'a>(nodes: impl Iterator<Item = NodeRef<'a, &'static str>>) -> Vec<&'static str> {
+        nodes.map(|node| *node).collect()
+    }
+
+    #[test]
+    fn build_tree() {
+        let arena = Arena::<4, _>::new();
+        let root = NodeRef::new(&arena, "root");
+        let a = NodeRef::new(&arena, "a");
+        let b = NodeRef::new(&arena, "b");
+        let c = NodeRef::new(&arena, "c");
+        root.append_child(a);
+        root.append_child(b);
+        b.append_child(c);
+
+        assert_eq!(data(root.children()), ["a", "b"]);
+        assert_eq!(data(b.children()), ["c"]);
+        assert!(a.parent().unwrap().ptr_eq(root));
+        assert!(a.next_sibling().unwrap().ptr_eq(b));
+        assert!(b.prev_sibling().unwrap().ptr_eq(a));
+        assert!(root.first_child().unwrap().ptr_eq(a));
+        assert!(root.last_child().unwrap().ptr_eq(b));
+        assert_eq!(data(c.ancestors()), ["b", "root"]);
+        assert_eq!(*c.data(), "c");
+    }
+
+    #[test]
+    fn detach_and_reattach() {
+        let arena = Arena::<4, _>::new();
+        let root = NodeRef::new(&arena, "root");
+        let a = NodeRef::new(&arena, "a");
+        let b = NodeRef::new(&arena, "b");
+        let c = NodeRef::new(&arena, "c");
+        root.append_child(a);
+        root.append_child(b);
+        root.append_child(c);
+
+        b.detach();
+        assert_eq!(data(root.children()), ["a", "c"]);
+        assert!(b.parent().is_none());
+        assert!(b.next_sibling().is_none());
+
+        // Moving a node to a different parent detaches it from the old one.
+        a.append_child(c);
+        assert_eq!(data(root.children()), ["a"]);
+        assert!(root.last_child().unwrap().ptr_eq(a));
+        assert_eq!(data(a.children()), ["c"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn append_ancestor() {
+        let arena = Arena::<4, _>::new();
+        let root = NodeRef::new(&arena, "root");
+        let a = NodeRef::new(&arena, "a");
+        root.append_child(a);
+        a.append_child(root);
+    }
+}
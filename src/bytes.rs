@@ -0,0 +1,369 @@
+use alloc::alloc::{alloc, dealloc, handle_alloc_error};
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::ptr::{self, NonNull};
+
+/// An arena for untyped memory, made up of a linked list of chunks with `N` bytes each.
+///
+/// Allocations are bump-allocated from the newest chunk and may have any size and
+/// alignment. Allocations that wouldn't fit into an empty chunk get a chunk of their own.
+/// The arena never runs destructors, so only `Copy` data can be stored in it.
+///
+/// [`SizeClassArena`] keeps separate chunks for allocations of different sizes.
+pub struct ByteArena<const N: usize> {
+    /// The newest chunk, which contains all other chunks in its linked list.
+    head: Cell<Option<NonNull<ChunkHeader>>>,
+    /// A pointer to the next free byte in the current chunk, or null if there is none.
+    ptr: Cell<*mut u8>,
+    /// A pointer to the end of the current chunk.
+    end: Cell<*mut u8>,
+}
+
+/// The header at the start of every chunk. The bytes of the chunk follow it.
+struct ChunkHeader {
+    next: Option<NonNull<ChunkHeader>>,
+    /// The layout of the whole chunk, including the header.
+    layout: Layout,
+}
+
+impl<const N: usize> ByteArena<N> {
+    /// Creates a new arena.
+    /// This function does not allocate any memory.
+    ///
+    /// ```compile_fail
+    /// let arena = linked_list_arena::bytes::ByteArena::<0>::new();
+    /// ```
+    pub fn new() -> Self {
+        const { crate::check_chunk_size::<N, u8>() };
+        ByteArena {
+            head: Cell::new(None),
+            ptr: Cell::new(ptr::null_mut()),
+            end: Cell::new(ptr::null_mut()),
+        }
+    }
+
+    /// Allocates memory for the given layout and returns a pointer to it.
+    ///
+    /// The memory is uninitialized and stays valid until the arena is dropped.
+    pub fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+        if layout.size() == 0 {
+            // Zero-sized allocations need no memory, only an aligned pointer.
+            return unsafe { NonNull::new_unchecked(ptr::without_provenance_mut(layout.align())) };
+        }
+        if let Some(ptr) = self.bump(layout) {
+            return ptr;
+        }
+        // Padding for the alignment is at most `align - 1` bytes.
+        if layout.size() + (layout.align() - 1) > N {
+            return self.alloc_oversized(layout);
+        }
+        self.push_chunk();
+        self.bump(layout)
+            .expect("a new chunk has room for the allocation")
+    }
+
+    /// Copies the slice into the arena and returns the copy.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_copy<T: Copy>(&self, src: &[T]) -> &mut [T] {
+        let ptr = self.alloc_layout(Layout::for_value(src)).cast::<T>();
+        unsafe {
+            ptr::copy_nonoverlapping(src.as_ptr(), ptr.as_ptr(), src.len());
+            core::slice::from_raw_parts_mut(ptr.as_ptr(), src.len())
+        }
+    }
+
+    /// Copies the string into the arena and returns the copy.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_str(&self, src: &str) -> &mut str {
+        let bytes = self.alloc_slice_copy(src.as_bytes());
+        // The bytes were copied from a `str`, so they are valid UTF-8.
+        unsafe { core::str::from_utf8_unchecked_mut(bytes) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.get().is_none()
+    }
+
+    /// Grows the newest allocation in place from `old_size` to `new_size` bytes, if it is at
+    /// the end of the used part of the current chunk and the chunk has enough room left.
+    ///
+    /// Returns whether the allocation was grown.
+    pub(crate) fn try_grow_last(&self, ptr: NonNull<u8>, old_size: usize, new_size: usize) -> bool {
+        let next = self.ptr.get();
+        if old_size == 0 || ptr.as_ptr().wrapping_add(old_size) != next {
+            return false;
+        }
+        if new_size - old_size > self.end.get() as usize - next as usize {
+            return false;
+        }
+        self.ptr.set(next.wrapping_add(new_size - old_size));
+        true
+    }
+
+    /// Takes memory for the layout from the current chunk, if it has enough room left.
+    fn bump(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let ptr = self.ptr.get();
+        if ptr.is_null() {
+            return None;
+        }
+        let addr = ptr as usize;
+        // The alignment is a power of two, so this rounds up to the next multiple of it.
+        let aligned_addr = addr.checked_add(layout.align() - 1)? & !(layout.align() - 1);
+        let new_addr = aligned_addr.checked_add(layout.size())?;
+        if new_addr > self.end.get() as usize {
+            return None;
+        }
+        // Deriving the pointers from `ptr` keeps the provenance of the chunk.
+        let start = ptr.wrapping_add(aligned_addr - addr);
+        self.ptr.set(ptr.wrapping_add(new_addr - addr));
+        NonNull::new(start)
+    }
+
+    /// Allocates a new chunk with `N` bytes and makes it the current chunk.
+    fn push_chunk(&self) {
+        let (header, data) = Self::new_chunk(Layout::array::<u8>(N).unwrap(), self.head.get());
+        self.head.set(Some(header));
+        self.ptr.set(data.as_ptr());
+        self.end.set(data.as_ptr().wrapping_add(N));
+    }
+
+    /// Allocates a chunk just for this layout and links it behind the current chunk, so
+    /// that the rest of the current chunk can still be used.
+    fn alloc_oversized(&self, layout: Layout) -> NonNull<u8> {
+        match self.head.get() {
+            Some(head) => unsafe {
+                let (header, data) = Self::new_chunk(layout, (*head.as_ptr()).next);
+                (*head.as_ptr()).next = Some(header);
+                data
+            },
+            None => {
+                // There is no current chunk yet, so this becomes the head. The next small
+                // allocation will push a regular chunk in front of it.
+                let (header, data) = Self::new_chunk(layout, None);
+                self.head.set(Some(header));
+                data
+            }
+        }
+    }
+
+    /// Allocates a chunk whose data part has the given layout and returns pointers to its
+    /// header and its data.
+    fn new_chunk(
+        data_layout: Layout,
+        next: Option<NonNull<ChunkHeader>>,
+    ) -> (NonNull<ChunkHeader>, NonNull<u8>) {
+        let (layout, offset) = Layout::new::<ChunkHeader>()
+            .extend(data_layout)
+            .expect("chunk size overflows `isize`");
+        let Some(base) = NonNull::new(unsafe { alloc(layout) }) else {
+            handle_alloc_error(layout);
+        };
+        let header = base.cast::<ChunkHeader>();
+        unsafe {
+            header.write(ChunkHeader { next, layout });
+            (header, base.add(offset))
+        }
+    }
+}
+
+impl<const N: usize> Default for ByteArena<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Drop for ByteArena<N> {
+    fn drop(&mut self) {
+        let mut cur_chunk = self.head.get();
+        while let Some(header) = cur_chunk {
+            unsafe {
+                let ChunkHeader { next, layout } = header.read();
+                dealloc(header.as_ptr().cast(), layout);
+                cur_chunk = next;
+            }
+        }
+    }
+}
+
+/// A byte arena with a separate list of chunks for each size class, so that allocations of
+/// similar sizes are stored next to each other.
+///
+/// Each allocation goes into the chunks of the smallest size class that its size fits
+/// into. Allocations that are larger than all size classes share one more list of chunks.
+/// Like in [`ByteArena`], every chunk has `N` bytes unless an allocation doesn't fit into
+/// it, and no destructors are run.
+pub struct SizeClassArena<const N: usize> {
+    /// The largest allocation size in bytes of each size class, in increasing order.
+    bounds: Vec<usize>,
+    /// The arena of each size class, followed by the one for larger allocations.
+    classes: Vec<ByteArena<N>>,
+}
+
+impl<const N: usize> SizeClassArena<N> {
+    /// The size classes of [`SizeClassArena::new`].
+    pub const DEFAULT_SIZE_CLASSES: [usize; 3] = [16, 64, 256];
+
+    /// Creates a new arena with the size classes [`SizeClassArena::DEFAULT_SIZE_CLASSES`].
+    pub fn new() -> Self {
+        Self::with_size_classes(&Self::DEFAULT_SIZE_CLASSES)
+    }
+
+    /// Creates a new arena whose size classes hold allocations of up to the given numbers
+    /// of bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bounds are not strictly increasing.
+    pub fn with_size_classes(bounds: &[usize]) -> Self {
+        assert!(
+            bounds.windows(2).all(|pair| pair[0] < pair[1]),
+            "the size classes must be strictly increasing"
+        );
+        SizeClassArena {
+            bounds: bounds.to_vec(),
+            classes: (0..=bounds.len()).map(|_| ByteArena::new()).collect(),
+        }
+    }
+
+    /// Returns the largest allocation size in bytes of each size class.
+    pub fn size_classes(&self) -> &[usize] {
+        &self.bounds
+    }
+
+    /// Allocates memory for the given layout in the chunks of its size class and returns
+    /// a pointer to it, see [`ByteArena::alloc_layout`].
+    pub fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+        let class = self.bounds.partition_point(|&bound| bound < layout.size());
+        self.classes[class].alloc_layout(layout)
+    }
+
+    /// Copies the slice into the arena and returns the copy.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_copy<T: Copy>(&self, src: &[T]) -> &mut [T] {
+        let ptr = self.alloc_layout(Layout::for_value(src)).cast::<T>();
+        unsafe {
+            ptr::copy_nonoverlapping(src.as_ptr(), ptr.as_ptr(), src.len());
+            core::slice::from_raw_parts_mut(ptr.as_ptr(), src.len())
+        }
+    }
+
+    /// Copies the string into the arena and returns the copy.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_str(&self, src: &str) -> &mut str {
+        let bytes = self.alloc_slice_copy(src.as_bytes());
+        // The bytes were copied from a `str`, so they are valid UTF-8.
+        unsafe { core::str::from_utf8_unchecked_mut(bytes) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.iter().all(ByteArena::is_empty)
+    }
+}
+
+impl<const N: usize> Default for SizeClassArena<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty_arena() {
+        let arena = ByteArena::<64>::new();
+        assert!(arena.is_empty());
+        assert_eq!(arena.alloc_str(""), "");
+        assert_eq!(arena.alloc_slice_copy::<u64>(&[]), [0u64; 0]);
+        assert!(arena.is_empty());
+    }
+
+    #[test]
+    fn alloc_strings_and_slices() {
+        let arena = ByteArena::<16>::new();
+        let hello = arena.alloc_str("hello");
+        let numbers = arena.alloc_slice_copy(&[1u32, 2, 3]);
+        let world = arena.alloc_str("world!");
+        hello.make_ascii_uppercase();
+        numbers[1] = 20;
+        assert!(!arena.is_empty());
+        assert_eq!(hello, "HELLO");
+        assert_eq!(numbers, [1, 20, 3]);
+        assert_eq!(world, "world!");
+        assert_eq!(numbers.as_ptr() as usize % std::mem::align_of::<u32>(), 0);
+    }
+
+    #[test]
+    fn alloc_layout_is_aligned() {
+        let arena = ByteArena::<256>::new();
+        for align in [1, 2, 8, 64] {
+            arena.alloc_str("x");
+            let layout = Layout::from_size_align(3, align).unwrap();
+            let ptr = arena.alloc_layout(layout);
+            assert_eq!(ptr.as_ptr() as usize % align, 0);
+            unsafe { ptr.as_ptr().write_bytes(0xff, 3) };
+        }
+        let zst = arena.alloc_layout(Layout::from_size_align(0, 32).unwrap());
+        assert_eq!(zst.as_ptr() as usize % 32, 0);
+    }
+
+    #[test]
+    fn oversized_allocations() {
+        let arena = ByteArena::<8>::new();
+        let big = arena.alloc_slice_copy(&[7u8; 100]);
+        let small = arena.alloc_str("abc");
+        let bigger = arena.alloc_str(&"y".repeat(20));
+        // The rest of the current chunk is still used.
+        let rest = arena.alloc_str("defg");
+        assert_eq!(big, [7; 100]);
+        assert_eq!(small, "abc");
+        assert_eq!(bigger.len(), 20);
+        assert_eq!(rest, "defg");
+        assert_eq!(unsafe { rest.as_ptr().offset_from(small.as_ptr()) }, 3);
+    }
+
+    #[test]
+    fn size_classes() {
+        let arena = SizeClassArena::<1024>::new();
+        assert!(arena.is_empty());
+        let small = arena.alloc_str("small");
+        let medium = arena.alloc_str(&"m".repeat(40));
+        let large = arena.alloc_str(&"l".repeat(300));
+        let tiny = arena.alloc_str("tiny");
+        let medium2 = arena.alloc_str(&"n".repeat(64));
+        assert!(!arena.is_empty());
+        // Allocations of the same size class are next to each other.
+        assert_eq!(unsafe { tiny.as_ptr().offset_from(small.as_ptr()) }, 5);
+        assert_eq!(unsafe { medium2.as_ptr().offset_from(medium.as_ptr()) }, 40);
+        assert_eq!(large.len(), 300);
+    }
+
+    #[test]
+    fn custom_size_classes() {
+        let arena = SizeClassArena::<64>::with_size_classes(&[8]);
+        assert_eq!(arena.size_classes(), [8]);
+        let numbers = arena.alloc_slice_copy(&[1u32, 2]);
+        let text = arena.alloc_str("more than eight");
+        let more = arena.alloc_slice_copy(&[3u32]);
+        assert_eq!(numbers, [1, 2]);
+        assert_eq!(text, "more than eight");
+        assert_eq!(unsafe { more.as_ptr().offset_from(numbers.as_ptr()) }, 2);
+    }
+
+    #[test]
+    #[should_panic = "the size classes must be strictly increasing"]
+    fn unsorted_size_classes() {
+        SizeClassArena::<64>::with_size_classes(&[64, 16]);
+    }
+
+    #[test]
+    fn many_chunks() {
+        let arena = ByteArena::<32>::new();
+        let strings: Vec<&str> = (0..100)
+            .map(|i| &*arena.alloc_str(&i.to_string()))
+            .collect();
+        assert!(strings.iter().copied().eq((0..100).map(|i| i.to_string())));
+    }
+}
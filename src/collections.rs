@@ -0,0 +1,377 @@
+//! Growable collections whose buffers are allocated in an arena, like
+//! `bumpalo::collections`: [`ArenaVec`], [`ArenaString`] and the [`format!`](crate::format)
+//! macro.
+//!
+//! The constructors take a [`ByteArena`] rather than an [`Arena`](crate::Arena). An
+//! `Arena<N, T>` only has fixed slots for single elements of type `T`, while the buffers of
+//! the collections need room for a varying number of elements of any type, and grow in
+//! place if they are the newest allocation.
+
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+
+use crate::bytes::ByteArena;
+
+/// A growable vector whose buffer is allocated in a [`ByteArena`].
+///
+/// The buffer is never freed on its own, but together with the arena. If the buffer is
+/// the newest allocation in the current chunk, it grows in place. Otherwise it is copied
+/// to a new, larger allocation, and the old buffer stays unused until the arena is
+/// dropped. The elements are dropped when the vector is dropped.
+pub struct ArenaVec<'a, const N: usize, T> {
+    arena: &'a ByteArena<N>,
+    ptr: NonNull<T>,
+    len: usize,
+    cap: usize,
+    /// The vector owns its elements.
+    _marker: PhantomData<T>,
+}
+
+impl<'a, const N: usize, T> ArenaVec<'a, N, T> {
+    const IS_ZST: bool = core::mem::size_of::<T>() == 0;
+
+    /// Creates a new, empty vector in the arena.
+    /// This function does not allocate any memory.
+    pub fn new_in(arena: &'a ByteArena<N>) -> Self {
+        ArenaVec {
+            arena,
+            ptr: NonNull::dangling(),
+            len: 0,
+            cap: if Self::IS_ZST { usize::MAX } else { 0 },
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a new vector in the arena with room for at least `capacity` elements.
+    pub fn with_capacity_in(capacity: usize, arena: &'a ByteArena<N>) -> Self {
+        let mut vec = Self::new_in(arena);
+        vec.reserve(capacity);
+        vec
+    }
+
+    /// Returns the number of elements the vector can hold without growing.
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    /// Makes room for at least `additional` more elements.
+    pub fn reserve(&mut self, additional: usize) {
+        let required = self.len.checked_add(additional).expect("capacity overflow");
+        if required <= self.cap {
+            return;
+        }
+        let new_cap = required.max(self.cap * 2).max(4);
+        let new_layout = Layout::array::<T>(new_cap).expect("capacity overflow");
+        let old_size = self.cap * core::mem::size_of::<T>();
+        if self
+            .arena
+            .try_grow_last(self.ptr.cast(), old_size, new_layout.size())
+        {
+            self.cap = new_cap;
+            return;
+        }
+        let new_ptr = self.arena.alloc_layout(new_layout).cast::<T>();
+        // The old buffer is left behind in the arena.
+        unsafe { ptr::copy_nonoverlapping(self.ptr.as_ptr(), new_ptr.as_ptr(), self.len) };
+        self.ptr = new_ptr;
+        self.cap = new_cap;
+    }
+
+    /// Appends an element to the back of the vector.
+    pub fn push(&mut self, elem: T) {
+        if self.len == self.cap {
+            self.reserve(1);
+        }
+        unsafe { self.ptr.add(self.len).write(elem) };
+        self.len += 1;
+    }
+
+    /// Removes the last element and returns it, or `None` if the vector is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        self.len = self.len.checked_sub(1)?;
+        Some(unsafe { self.ptr.add(self.len).read() })
+    }
+
+    /// Drops all elements, but keeps the buffer.
+    pub fn clear(&mut self) {
+        let elems = ptr::slice_from_raw_parts_mut(self.ptr.as_ptr(), self.len);
+        // Reset the length first, so that a panicking destructor can't lead to elements
+        // being dropped again.
+        self.len = 0;
+        unsafe { ptr::drop_in_place(elems) };
+    }
+
+    /// Clones and appends all elements of the slice.
+    pub fn extend_from_slice(&mut self, src: &[T])
+    where
+        T: Clone,
+    {
+        self.reserve(src.len());
+        for elem in src {
+            self.push(elem.clone());
+        }
+    }
+
+    /// Turns the vector into a slice that lives as long as the arena.
+    ///
+    /// The elements are not dropped anymore afterwards.
+    pub fn into_slice(self) -> &'a mut [T] {
+        let vec = ManuallyDrop::new(self);
+        unsafe { core::slice::from_raw_parts_mut(vec.ptr.as_ptr(), vec.len) }
+    }
+}
+
+impl<const N: usize, T> Deref for ArenaVec<'_, N, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<const N: usize, T> DerefMut for ArenaVec<'_, N, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<const N: usize, T> Extend<T> for ArenaVec<'_, N, T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+impl<const N: usize, T: fmt::Debug> fmt::Debug for ArenaVec<'_, N, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<const N: usize, T> Drop for ArenaVec<'_, N, T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// A growable string whose buffer is allocated in a [`ByteArena`].
+///
+/// See [`ArenaVec`] for how the buffer grows.
+pub struct ArenaString<'a, const N: usize> {
+    /// Always valid UTF-8.
+    vec: ArenaVec<'a, N, u8>,
+}
+
+impl<'a, const N: usize> ArenaString<'a, N> {
+    /// Creates a new, empty string in the arena.
+    /// This function does not allocate any memory.
+    pub fn new_in(arena: &'a ByteArena<N>) -> Self {
+        ArenaString {
+            vec: ArenaVec::new_in(arena),
+        }
+    }
+
+    /// Creates a new string in the arena with room for at least `capacity` bytes.
+    pub fn with_capacity_in(capacity: usize, arena: &'a ByteArena<N>) -> Self {
+        ArenaString {
+            vec: ArenaVec::with_capacity_in(capacity, arena),
+        }
+    }
+
+    /// Returns the number of bytes the string can hold without growing.
+    pub fn capacity(&self) -> usize {
+        self.vec.capacity()
+    }
+
+    /// Appends a character to the end of the string.
+    pub fn push(&mut self, ch: char) {
+        self.push_str(ch.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Appends a string slice to the end of the string.
+    pub fn push_str(&mut self, s: &str) {
+        self.vec.extend_from_slice(s.as_bytes());
+    }
+
+    pub fn as_str(&self) -> &str {
+        self
+    }
+
+    /// Turns the string into a string slice that lives as long as the arena.
+    pub fn into_str(self) -> &'a mut str {
+        let bytes = self.vec.into_slice();
+        unsafe { core::str::from_utf8_unchecked_mut(bytes) }
+    }
+}
+
+impl<const N: usize> Deref for ArenaString<'_, N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        unsafe { core::str::from_utf8_unchecked(&self.vec) }
+    }
+}
+
+impl<const N: usize> DerefMut for ArenaString<'_, N> {
+    fn deref_mut(&mut self) -> &mut str {
+        unsafe { core::str::from_utf8_unchecked_mut(&mut self.vec) }
+    }
+}
+
+impl<const N: usize> fmt::Write for ArenaString<'_, N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+impl<const N: usize> fmt::Display for ArenaString<'_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize> fmt::Debug for ArenaString<'_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// Creates an [`ArenaString`] in the given arena with the formatted text, like
+/// `bumpalo::format!`.
+///
+/// ```
+/// use linked_list_arena::bytes::ByteArena;
+///
+/// let arena = ByteArena::<64>::new();
+/// let answer = 42;
+/// let s = linked_list_arena::format!(in &arena, "the answer is {answer}");
+/// assert_eq!(s.as_str(), "the answer is 42");
+/// ```
+///
+/// Without `in`, this is the standard `format!`, so importing this macro doesn't hide it.
+#[macro_export]
+macro_rules! format {
+    (in $arena:expr, $($arg:tt)*) => {{
+        let mut s = $crate::collections::ArenaString::new_in($arena);
+        ::core::fmt::Write::write_fmt(&mut s, ::core::format_args!($($arg)*))
+            .expect("a formatting trait implementation returned an error");
+        s
+    }};
+    ($($arg:tt)*) => {
+        $crate::collections::__alloc_format!($($arg)*)
+    };
+}
+
+#[doc(hidden)]
+pub use alloc::format as __alloc_format;
+
+#[cfg(test)]
+mod test {
+    use std::fmt::Write;
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn push_and_pop() {
+        let arena = ByteArena::<64>::new();
+        let mut vec = ArenaVec::new_in(&arena);
+        assert_eq!(vec.capacity(), 0);
+        for i in 0..10 {
+            vec.push(i);
+        }
+        assert_eq!(*vec, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(vec.pop(), Some(9));
+        vec[0] = 10;
+        vec.extend(20..22);
+        assert_eq!(format!("{vec:?}"), "[10, 1, 2, 3, 4, 5, 6, 7, 8, 20, 21]");
+        vec.clear();
+        assert_eq!(vec.pop(), None);
+    }
+
+    #[test]
+    fn grow_in_place() {
+        let arena = ByteArena::<64>::new();
+        let mut vec = ArenaVec::<64, u32>::with_capacity_in(2, &arena);
+        vec.extend_from_slice(&[1, 2]);
+        let ptr = vec.as_ptr();
+        vec.push(3);
+        // Nothing else was allocated, so the buffer didn't move.
+        assert_eq!(vec.as_ptr(), ptr);
+        assert!(vec.capacity() > 2);
+
+        let other = arena.alloc_str("x");
+        vec.reserve(vec.capacity());
+        assert_ne!(vec.as_ptr(), ptr);
+        assert_eq!(*vec, [1, 2, 3]);
+        assert_eq!(other, "x");
+    }
+
+    #[test]
+    fn grow_beyond_chunk() {
+        let arena = ByteArena::<16>::new();
+        let mut vec = ArenaVec::new_in(&arena);
+        let mut expected = Vec::new();
+        for i in 0..100u64 {
+            vec.push(i.to_string());
+            expected.push(i.to_string());
+        }
+        assert_eq!(*vec, *expected);
+        assert_eq!(vec.as_ptr() as usize % std::mem::align_of::<String>(), 0);
+    }
+
+    #[test]
+    fn drop_elements() {
+        let counter = Rc::new(());
+        let arena = ByteArena::<64>::new();
+        let mut vec = ArenaVec::new_in(&arena);
+        vec.extend((0..10).map(|_| Rc::clone(&counter)));
+        assert_eq!(Rc::strong_count(&counter), 11);
+        drop(vec);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn zero_sized_types() {
+        let arena = ByteArena::<64>::new();
+        let mut vec = ArenaVec::new_in(&arena);
+        for _ in 0..5 {
+            vec.push(());
+        }
+        assert_eq!((vec.len(), vec.capacity()), (5, usize::MAX));
+        assert!(arena.is_empty());
+    }
+
+    #[test]
+    fn string() {
+        let arena = ByteArena::<32>::new();
+        let mut s = ArenaString::new_in(&arena);
+        s.push_str("hello");
+        s.push(',');
+        s.push(' ');
+        let name = "wörld";
+        write!(s, "{name}!").unwrap();
+        assert_eq!(s.as_str(), "hello, wörld!");
+        assert_eq!(format!("{s} {s:?}"), "hello, wörld! \"hello, wörld!\"");
+        s.make_ascii_uppercase();
+        let s: &str = s.into_str();
+        assert_eq!(s, "HELLO, WöRLD!");
+    }
+
+    #[test]
+    fn format_macro() {
+        let arena = ByteArena::<16>::new();
+        let numbers = [1, 2, 3];
+        let s = crate::format!(in &arena, "{numbers:?} and {}", "more than one chunk");
+        assert_eq!(s.as_str(), "[1, 2, 3] and more than one chunk");
+        assert_eq!(crate::format!(in &arena, "").capacity(), 0);
+    }
+}
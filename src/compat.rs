@@ -0,0 +1,7 @@
+//! Wrappers with the APIs of other arena crates, so that code written against them can
+//! switch to the chunked arenas of this crate by changing its imports, e.g. to compare
+//! their performance.
+//!
+//! The API of the `id_arena` crate is provided by [`crate::id_arena`].
+
+pub mod typed_arena;
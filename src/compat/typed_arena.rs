@@ -0,0 +1,127 @@
+//! A wrapper with the API of the `typed-arena` crate, backed by an [`Arena`](crate::Arena).
+//!
+//! Unlike `typed_arena::Arena`, the chunks don't grow: every chunk has [`CHUNK_LEN`] slots,
+//! except the ones that [`Arena::alloc_extend`] allocates for more elements. There is no
+//! const parameter for the chunk length, because `Arena::new()` couldn't infer it.
+
+use alloc::vec::Vec;
+
+use crate::IterMut;
+
+/// The number of slots in each chunk.
+pub const CHUNK_LEN: usize = 64;
+
+/// An arena of objects of type `T`, like `typed_arena::Arena`.
+pub struct Arena<T> {
+    inner: crate::Arena<CHUNK_LEN, T>,
+}
+
+impl<T> Arena<T> {
+    /// Creates a new arena.
+    /// This function does not allocate any memory.
+    pub fn new() -> Self {
+        Arena {
+            inner: crate::Arena::new(),
+        }
+    }
+
+    /// Creates a new arena with room for at least `n` elements.
+    pub fn with_capacity(n: usize) -> Self {
+        Arena {
+            inner: crate::Arena::with_capacity(n),
+        }
+    }
+
+    /// Allocates a new element in the arena and returns a mutable reference to it.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc(&self, value: T) -> &mut T {
+        self.inner.alloc(value)
+    }
+
+    /// Allocates the elements of an iterator next to each other and returns them as a
+    /// slice.
+    ///
+    /// If the size hint of the iterator gives its exact length, the iterator must not use
+    /// the arena; doing so panics. See [`crate::Arena::alloc_extend`].
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_extend(&self, iterable: impl IntoIterator<Item = T>) -> &mut [T] {
+        self.inner.alloc_extend(iterable)
+    }
+
+    /// Returns the number of elements in the arena.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Returns an iterator over mutable references to all elements, in allocation order.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        self.inner.iter_mut()
+    }
+
+    /// Consumes the arena and returns its elements in allocation order.
+    pub fn into_vec(self) -> Vec<T> {
+        self.inner.drain_destroy().collect()
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use super::Arena;
+
+    #[test]
+    fn alloc_and_into_vec() {
+        let arena = Arena::new();
+        arena.alloc(String::from("a"));
+        let slice = arena.alloc_extend((0..100).map(|i| i.to_string()));
+        slice[0].push('!');
+        assert_eq!(arena.len(), 101);
+        let elems = arena.into_vec();
+        assert_eq!(elems[..3], ["a", "0!", "1"]);
+        assert_eq!(elems[100], "99");
+    }
+
+    #[test]
+    fn elements_refer_to_each_other() {
+        struct Node<'a> {
+            value: i32,
+            next: Cell<Option<&'a Node<'a>>>,
+        }
+
+        let arena = Arena::with_capacity(2);
+        let a = arena.alloc(Node {
+            value: 1,
+            next: Cell::new(None),
+        });
+        let b = arena.alloc(Node {
+            value: 2,
+            next: Cell::new(Some(a)),
+        });
+        a.next.set(Some(b));
+        assert_eq!(a.next.get().map(|node| node.value), Some(2));
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut arena = Arena::new();
+        arena.alloc_extend(0..100);
+        for elem in arena.iter_mut() {
+            *elem *= 2;
+        }
+        assert!(arena
+            .iter_mut()
+            .map(|elem| *elem)
+            .eq((0..100).map(|i| i * 2)));
+    }
+}
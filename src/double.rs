@@ -1,102 +1,486 @@
-// use std::array;
-use std::cell::{Cell, RefCell};
-// use std::collections::linked_list;
-use std::collections::LinkedList;
-use std::mem::MaybeUninit;
-use std::ptr::NonNull;
+use alloc::alloc::{alloc, dealloc, handle_alloc_error};
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr::{addr_of_mut, NonNull};
 
+/// An arena made up of a doubly-linked list of chunks with `N` slots each.
+///
+/// The chunks are linked in both directions, so the elements can be iterated from either
+/// end.
 pub struct DoublyLinkedArena<const N: usize, T> {
-    list: RefCell<LinkedList<[MaybeUninit<T>; N]>>,
+    chunks: ErasedList,
+    /// The arena owns its elements, and `Cell` makes it invariant in `T`.
+    _marker: PhantomData<Cell<T>>,
+}
+
+/// The chunk list of a [`DoublyLinkedArena`], with a type that doesn't mention `T`.
+///
+/// See `ErasedInner` in the crate root for why the elements are dropped through a
+/// function pointer.
+struct ErasedList {
+    /// The oldest chunk.
+    head: Cell<Option<NonNull<Links>>>,
+    /// The newest chunk, which elements are allocated from.
+    tail: Cell<Option<NonNull<Links>>>,
     /// A pointer to the next object to be allocated.
-    ptr: Cell<Option<NonNull<MaybeUninit<T>>>>,
+    ptr: Cell<Option<NonNull<()>>>,
     /// A pointer to the end of the current chunk.
-    end: Cell<Option<NonNull<MaybeUninit<T>>>>,
+    end: Cell<Option<NonNull<()>>>,
+    chunk_count: Cell<usize>,
+    /// The number of elements if `T` is zero-sized. No chunks are allocated for them.
+    zst_len: Cell<usize>,
+    drop_chunks: unsafe fn(&mut ErasedList),
+}
+
+/// A chunk of a [`DoublyLinkedArena`].
+///
+/// A pointer to the links is also a pointer to the chunk, so the list can link chunks
+/// without knowing `T`.
+#[repr(C)]
+struct Chunk<const N: usize, T> {
+    links: Links,
+    slots: [MaybeUninit<T>; N],
+}
+
+/// The links of a chunk to its neighbors.
+///
+/// They are only accessed through raw pointers, because the slots next to them may be
+/// borrowed.
+struct Links {
+    /// The next older chunk.
+    prev: Option<NonNull<Links>>,
+    /// The next newer chunk.
+    next: Option<NonNull<Links>>,
+}
+
+impl<const N: usize, T> Chunk<N, T> {
+    /// Allocates a chunk with the given links. The slots stay uninitialized.
+    fn allocate(links: Links) -> NonNull<Links> {
+        let layout = Layout::new::<Self>();
+        let Some(chunk) = NonNull::new(unsafe { alloc(layout) }) else {
+            handle_alloc_error(layout);
+        };
+        let chunk = chunk.cast::<Links>();
+        unsafe { chunk.write(links) };
+        chunk
+    }
+
+    /// Returns a pointer to the first slot of the chunk.
+    ///
+    /// # Safety
+    ///
+    /// The chunk must have been allocated by [`Chunk::allocate`] with the same `N` and `T`.
+    unsafe fn slots(chunk: NonNull<Links>) -> NonNull<MaybeUninit<T>> {
+        NonNull::new_unchecked(addr_of_mut!((*chunk.cast::<Self>().as_ptr()).slots)).cast()
+    }
+
+    /// Frees the chunk without touching its slots.
+    ///
+    /// # Safety
+    ///
+    /// The chunk must have been allocated by [`Chunk::allocate`] with the same `N` and `T`, and
+    /// it must not be used afterwards.
+    unsafe fn free(chunk: NonNull<Links>) {
+        dealloc(chunk.cast().as_ptr(), Layout::new::<Self>());
+    }
 }
 
 impl<const N: usize, T> DoublyLinkedArena<N, T> {
     /// Creates a new arena.
     /// This function does not allocate any memory.
+    ///
+    /// ```compile_fail
+    /// let arena = linked_list_arena::double::DoublyLinkedArena::<0, i32>::new();
+    /// ```
     pub fn new() -> Self {
-        assert!(std::mem::size_of::<T>() != 0);
-        assert!(N != 0);
+        const { crate::check_chunk_size::<N, T>() };
         DoublyLinkedArena {
-            list: RefCell::new(LinkedList::new()),
-            ptr: Cell::new(None),
-            end: Cell::new(None),
+            chunks: ErasedList {
+                head: Cell::new(None),
+                tail: Cell::new(None),
+                ptr: Cell::new(None),
+                end: Cell::new(None),
+                chunk_count: Cell::new(0),
+                zst_len: Cell::new(0),
+                drop_chunks: drop_chunks::<N, T>,
+            },
+            _marker: PhantomData,
         }
     }
 
     /// Allocates a new element in the arena and returns a mutable reference to it.
     #[allow(clippy::mut_from_ref)]
     pub fn alloc(&self, elem: T) -> &mut T {
+        if core::mem::size_of::<T>() == 0 {
+            let zst_len = self
+                .chunks
+                .zst_len
+                .get()
+                .checked_add(1)
+                .expect("too many elements");
+            self.chunks.zst_len.set(zst_len);
+            return unsafe { NonNull::<MaybeUninit<T>>::dangling().as_mut() }.write(elem);
+        }
         // Check whether anything has been allocated yet.
-        if let Some(mut ptr) = self.ptr.get() {
-            let end = self.end.get().unwrap();
+        if let Some(mut ptr) = self.ptr() {
+            let end = self.end().unwrap();
             // Check whether there is still space in the current chunk.
             if ptr < end {
                 let slot = unsafe {
                     // Advance the pointer and turn the pointer into a mutable reference.
-                    self.ptr.set(Some(ptr.add(1)));
+                    self.chunks.ptr.set(Some(ptr.add(1).cast()));
                     ptr.as_mut()
                 };
                 return slot.write(elem);
             }
         }
-        // Allocate a new chunk.
-        let mut list = self.list.borrow_mut();
-        list.push_back([const { MaybeUninit::uninit() }; N]);
-        unsafe {
-            let ptr = NonNull::new_unchecked(list.back_mut().unwrap().as_mut_ptr());
-            self.ptr.set(Some(ptr));
-            self.end.set(ptr.add(N).into());
-        }
+        self.push_chunk();
         // Recurse to allocate the element in the new chunk.
         self.alloc(elem)
     }
 
     pub fn is_empty(&self) -> bool {
-        self.list.borrow().is_empty()
+        self.chunks.head.get().is_none() && self.chunks.zst_len.get() == 0
+    }
+
+    /// Returns the number of elements in the arena.
+    pub fn len(&self) -> usize {
+        // All chunks except the last one are full.
+        let free_slots = self.free_slots_in_current_chunk().unwrap_or(0);
+        self.chunk_count() * N - free_slots + self.chunks.zst_len.get()
+    }
+
+    /// Returns the number of chunks the arena has allocated.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.chunk_count.get()
+    }
+
+    /// Returns the total number of slots in all chunks. For zero-sized types, this is
+    /// `usize::MAX`.
+    pub fn capacity(&self) -> usize {
+        if core::mem::size_of::<T>() == 0 {
+            return usize::MAX;
+        }
+        self.chunk_count() * N
     }
 
     /// Returns the number of free slots in the current chunk.
-    /// If no chunk has been allocated yet, `None` is returned.
+    /// If no chunk has been allocated yet, which is always the case for zero-sized types,
+    /// `None` is returned.
     pub fn free_slots_in_current_chunk(&self) -> Option<usize> {
-        self.end
-            .get()
-            .map(|end| unsafe { end.offset_from(self.ptr.get().unwrap()) as usize })
-    }
-
-    // pub fn into_iter(self) -> IntoIter<N, T> {
-    //     IntoIter {
-    //         list_iter: self.list.into_inner().into_iter(),
-    //         chunk_iter: None,
-    //         ptr: self.ptr.get(),
-    //     }
-    // }
-}
-
-// pub struct IntoIter<const N: usize, T> {
-//     list_iter: linked_list::IntoIter<[MaybeUninit<T>; N]>,
-//     chunk_iter: Option<array::IntoIter<MaybeUninit<T>, N>>,
-//     ptr: Option<NonNull<MaybeUninit<T>>>,
-// }
-
-// impl<const N: usize, T> Iterator for IntoIter<N, T> {
-//     type Item = T;
-
-//     fn next(&mut self) -> Option<Self::Item> {
-//         if let Some(mut chunk_iter) = &mut self.chunk_iter {
-//             if let Some(slot) = chunk_iter.next() {
-//                 return Some(unsafe { slot.assume_init() });
-//             }
-//         }
-//         todo!()
-//     }
-// }
+        self.end()
+            .map(|end| unsafe { end.offset_from(self.ptr().unwrap()) as usize })
+    }
+
+    /// Returns an iterator over all elements, in allocation order.
+    ///
+    /// This takes `&mut self` for the same reason as [`Arena::iter`](crate::Arena::iter).
+    pub fn iter(&mut self) -> impl DoubleEndedIterator<Item = &T> + '_ {
+        self.iter_mut().map(|elem| &*elem)
+    }
+
+    /// Returns an iterator over mutable references to all elements, in allocation order.
+    ///
+    /// The iterator can also walk the chunks backwards, from the newest element.
+    pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut T> + '_ {
+        let mut slices = Vec::with_capacity(self.chunk_count());
+        if core::mem::size_of::<T>() == 0 {
+            slices.push(NonNull::slice_from_raw_parts(
+                NonNull::dangling(),
+                self.chunks.zst_len.get(),
+            ));
+        }
+        let mut cur_chunk = self.chunks.head.get();
+        while let Some(chunk) = cur_chunk {
+            unsafe {
+                cur_chunk = (*chunk.as_ptr()).next;
+                let len = if cur_chunk.is_some() {
+                    N
+                } else {
+                    N - self.free_slots_in_current_chunk().unwrap()
+                };
+                let start = Chunk::<N, T>::slots(chunk).cast::<T>();
+                slices.push(NonNull::slice_from_raw_parts(start, len));
+            }
+        }
+        // The arena is borrowed mutably, so the slices are unique.
+        slices
+            .into_iter()
+            .flat_map(|slice| unsafe { &mut *slice.as_ptr() }.iter_mut())
+    }
+
+    /// Returns a reference to the element with the given index, where the index counts the
+    /// elements in allocation order.
+    ///
+    /// This takes `&mut self` for the same reason as [`Arena::iter`](crate::Arena::iter).
+    /// All chunks except the last one are full, so the chunk of the element is known, and
+    /// it is reached from whichever end of the list is closer.
+    pub fn get(&mut self, index: usize) -> Option<&T> {
+        self.element_ptr(index).map(|ptr| unsafe { ptr.as_ref() })
+    }
+
+    /// Returns a mutable reference to the element with the given index, see
+    /// [`DoublyLinkedArena::get`].
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.element_ptr(index)
+            .map(|mut ptr| unsafe { ptr.as_mut() })
+    }
+
+    /// Returns a pointer to the element with the given index, in allocation order.
+    fn element_ptr(&self, index: usize) -> Option<NonNull<T>> {
+        if index >= self.len() {
+            return None;
+        }
+        if core::mem::size_of::<T>() == 0 {
+            return Some(NonNull::dangling());
+        }
+        let chunk_index = index / N;
+        let chunk_count = self.chunk_count();
+        let chunk = unsafe {
+            if chunk_index < chunk_count / 2 {
+                let mut chunk = self.chunks.head.get()?;
+                for _ in 0..chunk_index {
+                    chunk = (*chunk.as_ptr()).next?;
+                }
+                chunk
+            } else {
+                let mut chunk = self.chunks.tail.get()?;
+                for _ in chunk_index + 1..chunk_count {
+                    chunk = (*chunk.as_ptr()).prev?;
+                }
+                chunk
+            }
+        };
+        Some(unsafe { Chunk::<N, T>::slots(chunk).cast::<T>().add(index % N) })
+    }
+
+    /// Allocates a new chunk and appends it to the list.
+    fn push_chunk(&self) {
+        let tail = self.chunks.tail.get();
+        let chunk = Chunk::<N, T>::allocate(Links {
+            prev: tail,
+            next: None,
+        });
+        match tail {
+            // The slots of the old tail may be borrowed, so only its links are written.
+            Some(tail) => unsafe { (*tail.as_ptr()).next = Some(chunk) },
+            None => self.chunks.head.set(Some(chunk)),
+        }
+        self.chunks.tail.set(Some(chunk));
+        self.chunks
+            .chunk_count
+            .set(self.chunks.chunk_count.get() + 1);
+        unsafe {
+            let ptr = Chunk::<N, T>::slots(chunk);
+            self.chunks.ptr.set(Some(ptr.cast()));
+            self.chunks.end.set(Some(ptr.add(N).cast()));
+        }
+    }
+
+    fn ptr(&self) -> Option<NonNull<MaybeUninit<T>>> {
+        self.chunks.ptr.get().map(NonNull::cast)
+    }
+
+    fn end(&self) -> Option<NonNull<MaybeUninit<T>>> {
+        self.chunks.end.get().map(NonNull::cast)
+    }
+}
+
+impl<const N: usize, T> Default for DoublyLinkedArena<N, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Allocates the elements one chunk at a time, writing directly to the free slots of the
+/// current chunk.
+impl<const N: usize, T> Extend<T> for DoublyLinkedArena<N, T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let mut iter = iter.into_iter();
+        if core::mem::size_of::<T>() == 0 {
+            for elem in iter {
+                self.alloc(elem);
+            }
+            return;
+        }
+        while let Some(first) = iter.next() {
+            // Allocating the first element makes sure that there is a current chunk.
+            self.alloc(first);
+            let (mut ptr, end) = (self.ptr().unwrap(), self.end().unwrap());
+            let free_slots = unsafe { end.offset_from(ptr) as usize };
+            for elem in iter.by_ref().take(free_slots) {
+                unsafe {
+                    ptr.write(MaybeUninit::new(elem));
+                    ptr = ptr.add(1);
+                }
+                self.chunks.ptr.set(Some(ptr.cast()));
+            }
+        }
+    }
+}
+
+impl<const N: usize, T> FromIterator<T> for DoublyLinkedArena<N, T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut arena = Self::new();
+        arena.extend(iter);
+        arena
+    }
+}
+
+/// Drops all elements and frees all chunks of a `DoublyLinkedArena<N, T>`.
+///
+/// # Safety
+///
+/// The chunks must have been created by a `DoublyLinkedArena<N, T>`.
+unsafe fn drop_chunks<const N: usize, T>(list: &mut ErasedList) {
+    if core::mem::size_of::<T>() == 0 {
+        crate::drop_zsts::<T>(list.zst_len.take());
+        return;
+    }
+    let end = list.ptr.get().map(NonNull::cast::<MaybeUninit<T>>);
+    let mut cur_chunk = list.head.take();
+    while let Some(chunk) = cur_chunk {
+        cur_chunk = (*chunk.as_ptr()).next;
+        let start = Chunk::<N, T>::slots(chunk);
+        // All chunks except the last one are full.
+        let len = match cur_chunk {
+            Some(_) => N,
+            None => end.unwrap().offset_from(start) as usize,
+        };
+        let elems = core::ptr::slice_from_raw_parts_mut(start.cast::<T>().as_ptr(), len);
+        core::ptr::drop_in_place(elems);
+        Chunk::<N, T>::free(chunk);
+    }
+}
+
+impl Drop for ErasedList {
+    fn drop(&mut self) {
+        unsafe { (self.drop_chunks)(self) }
+    }
+}
+
+impl<const N: usize, T> IntoIterator for DoublyLinkedArena<N, T> {
+    type Item = T;
+    type IntoIter = IntoIter<N, T>;
+
+    /// Consumes the arena and returns an iterator that moves the elements out of it, in
+    /// allocation order.
+    fn into_iter(self) -> IntoIter<N, T> {
+        let back_len = N - self.free_slots_in_current_chunk().unwrap_or(N);
+        // The iterator takes over the chunks, so the arena must not drop them.
+        let chunks = &self.chunks;
+        IntoIter {
+            front: chunks.head.take(),
+            front_index: 0,
+            back: chunks.tail.take(),
+            back_len,
+            zst_len: chunks.zst_len.take(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// An iterator that moves the elements out of a [`DoublyLinkedArena`].
+///
+/// Each chunk is freed as soon as all of its elements have been yielded. Elements that are
+/// not consumed are dropped together with the iterator.
+pub struct IntoIter<const N: usize, T> {
+    /// The oldest remaining chunk.
+    front: Option<NonNull<Links>>,
+    /// The index of the next element in the front chunk.
+    front_index: usize,
+    /// The newest remaining chunk.
+    back: Option<NonNull<Links>>,
+    /// The number of slots in the back chunk that haven't been yielded from the back.
+    back_len: usize,
+    /// The number of remaining elements if `T` is zero-sized.
+    zst_len: usize,
+    /// The iterator owns the remaining elements.
+    _marker: PhantomData<T>,
+}
+
+// The iterator owns the elements and the chunks, like a `vec::IntoIter<T>`.
+unsafe impl<const N: usize, T: Send> Send for IntoIter<N, T> {}
+unsafe impl<const N: usize, T: Sync> Sync for IntoIter<N, T> {}
+
+impl<const N: usize, T> IntoIter<N, T> {
+    /// Frees the last remaining chunk once both ends have met in it.
+    fn free_last_chunk(&mut self, chunk: NonNull<Links>) {
+        self.front = None;
+        self.back = None;
+        unsafe { Chunk::<N, T>::free(chunk) };
+    }
+}
+
+impl<const N: usize, T> Iterator for IntoIter<N, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.zst_len > 0 {
+            self.zst_len -= 1;
+            return Some(unsafe { NonNull::dangling().read() });
+        }
+        loop {
+            let front = self.front?;
+            let is_last = Some(front) == self.back;
+            let end = if is_last { self.back_len } else { N };
+            if self.front_index < end {
+                let slot = unsafe { Chunk::<N, T>::slots(front).add(self.front_index) };
+                self.front_index += 1;
+                // Every initialized slot is yielded exactly once.
+                return Some(unsafe { slot.read().assume_init() });
+            }
+            if is_last {
+                self.free_last_chunk(front);
+                return None;
+            }
+            self.front = unsafe { (*front.as_ptr()).next };
+            self.front_index = 0;
+            unsafe { Chunk::<N, T>::free(front) };
+        }
+    }
+}
+
+impl<const N: usize, T> DoubleEndedIterator for IntoIter<N, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.zst_len > 0 {
+            self.zst_len -= 1;
+            return Some(unsafe { NonNull::dangling().read() });
+        }
+        loop {
+            let back = self.back?;
+            let is_last = Some(back) == self.front;
+            let start = if is_last { self.front_index } else { 0 };
+            if self.back_len > start {
+                self.back_len -= 1;
+                let slot = unsafe { Chunk::<N, T>::slots(back).add(self.back_len) };
+                return Some(unsafe { slot.read().assume_init() });
+            }
+            if is_last {
+                self.free_last_chunk(back);
+                return None;
+            }
+            // Chunks before the back chunk are full.
+            self.back = unsafe { (*back.as_ptr()).prev };
+            self.back_len = N;
+            unsafe { Chunk::<N, T>::free(back) };
+        }
+    }
+}
+
+impl<const N: usize, T> Drop for IntoIter<N, T> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
 
 #[cfg(test)]
 mod test {
     use std::cell::Cell;
+    use std::rc::Rc;
 
     use super::*;
 
@@ -154,7 +538,146 @@ mod test {
     #[test]
     fn data_structure_size() {
         assert_eq!(std::mem::size_of::<usize>(), 8);
-        assert_eq!(std::mem::size_of::<DoublyLinkedArena<1, i32>>(), 48);
+        assert_eq!(std::mem::size_of::<DoublyLinkedArena<1, i32>>(), 56);
+    }
+
+    #[test]
+    fn into_iter() {
+        let arena = DoublyLinkedArena::<3, String>::new();
+        assert_eq!(
+            DoublyLinkedArena::<3, String>::new().into_iter().next(),
+            None
+        );
+        for i in 0..7 {
+            arena.alloc(i.to_string());
+        }
+        assert!(arena
+            .into_iter()
+            .eq(["0", "1", "2", "3", "4", "5", "6"].map(String::from)));
+    }
+
+    #[test]
+    fn len_and_capacity() {
+        let arena = DoublyLinkedArena::<3, i32>::new();
+        assert_eq!(
+            (arena.len(), arena.chunk_count(), arena.capacity()),
+            (0, 0, 0)
+        );
+        for i in 0..4 {
+            arena.alloc(i);
+        }
+        assert_eq!(
+            (arena.len(), arena.chunk_count(), arena.capacity()),
+            (4, 2, 6)
+        );
+        for i in 4..6 {
+            arena.alloc(i);
+        }
+        assert_eq!(
+            (arena.len(), arena.chunk_count(), arena.capacity()),
+            (6, 2, 6)
+        );
+    }
+
+    #[test]
+    fn zero_sized_types() {
+        let arena = DoublyLinkedArena::<3, ()>::new();
+        assert!(arena.is_empty());
+        for _ in 0..5 {
+            arena.alloc(());
+        }
+        assert!(!arena.is_empty());
+        assert_eq!(arena.free_slots_in_current_chunk(), None);
+        assert_eq!((arena.len(), arena.capacity()), (5, usize::MAX));
+        assert_eq!(arena.into_iter().count(), 5);
+    }
+
+    #[test]
+    fn drop_partially_consumed_into_iter() {
+        let drop_counter = Rc::new(Cell::new(0));
+
+        let arena = DoublyLinkedArena::<2, Rc<Cell<i32>>>::new();
+        for _ in 0..5 {
+            arena.alloc(Rc::clone(&drop_counter));
+        }
+        let mut iter = arena.into_iter();
+        iter.next();
+        iter.next();
+        iter.next();
+        assert_eq!(Rc::strong_count(&drop_counter), 3);
+        drop(iter);
+        assert_eq!(Rc::strong_count(&drop_counter), 1);
+    }
+
+    #[test]
+    fn into_iter_from_both_ends() {
+        let arena = DoublyLinkedArena::<3, String>::new();
+        for i in 0..8 {
+            arena.alloc(i.to_string());
+        }
+        let mut iter = arena.into_iter();
+        assert_eq!(iter.next_back().as_deref(), Some("7"));
+        assert_eq!(iter.next().as_deref(), Some("0"));
+        assert_eq!(iter.next_back().as_deref(), Some("6"));
+        assert_eq!(iter.next_back().as_deref(), Some("5"));
+        assert!(iter.eq(["1", "2", "3", "4"].map(String::from)));
+
+        let arena = DoublyLinkedArena::<2, i32>::new();
+        for i in 0..5 {
+            arena.alloc(i);
+        }
+        assert!(arena.into_iter().rev().eq((0..5).rev()));
+    }
+
+    #[test]
+    fn extend_and_collect() {
+        let mut arena: DoublyLinkedArena<3, String> = (0..4).map(|i| i.to_string()).collect();
+        assert_eq!((arena.len(), arena.chunk_count()), (4, 2));
+        arena.extend((4..9).map(|i| i.to_string()));
+        assert_eq!((arena.len(), arena.chunk_count()), (9, 3));
+        assert!(arena.into_iter().eq((0..9).map(|i| i.to_string())));
+
+        let arena: DoublyLinkedArena<3, ()> = std::iter::repeat_n((), 5).collect();
+        assert_eq!(arena.len(), 5);
+    }
+
+    #[test]
+    fn iter_mut_from_both_ends() {
+        let mut arena = DoublyLinkedArena::<3, i32>::new();
+        for i in 0..7 {
+            arena.alloc(i);
+        }
+        arena.iter_mut().rev().take(2).for_each(|x| *x *= 10);
+        assert!(arena.iter().copied().eq([0, 1, 2, 3, 4, 50, 60]));
+        assert!(arena.iter().rev().copied().eq([60, 50, 4, 3, 2, 1, 0]));
+    }
+
+    #[test]
+    fn get_by_index() {
+        let mut arena: DoublyLinkedArena<3, i32> = (0..14).collect();
+        for i in 0..14 {
+            assert_eq!(arena.get(i), Some(&(i as i32)));
+        }
+        assert_eq!(arena.get(14), None);
+        *arena.get_mut(13).unwrap() = 130;
+        assert_eq!(arena.iter().next_back(), Some(&130));
+
+        let mut zsts: DoublyLinkedArena<3, ()> = std::iter::repeat_n((), 2).collect();
+        assert_eq!(zsts.get(1), Some(&()));
+        assert_eq!(zsts.get(2), None);
+    }
+
+    #[test]
+    fn drop_elements() {
+        let drop_counter = Rc::new(Cell::new(0));
+
+        let arena = DoublyLinkedArena::<2, Rc<Cell<i32>>>::new();
+        for _ in 0..5 {
+            arena.alloc(Rc::clone(&drop_counter));
+        }
+        assert_eq!(Rc::strong_count(&drop_counter), 6);
+        drop(arena);
+        assert_eq!(Rc::strong_count(&drop_counter), 1);
     }
 
     struct CycleParticipant<'a> {
@@ -0,0 +1,496 @@
+use alloc::alloc::{alloc, dealloc, handle_alloc_error};
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+/// An arena whose chunk capacity is chosen at runtime instead of being part of the type.
+///
+/// The chunks either all have the same capacity, or every chunk has twice the capacity of
+/// the previous one, so that the number of chunks only grows logarithmically with the
+/// number of elements. Doubling can be capped at a maximum capacity, which keeps the first
+/// chunk of an arena that usually stays tiny small without giving up large chunks later:
+///
+/// ```
+/// use linked_list_arena::dynamic::DynArena;
+///
+/// // Start with 8 slots and grow to chunks of 64 slots, like an `Arena<64, u32>`.
+/// let arena = DynArena::<u32>::with_doubling_chunks_up_to(8, 64);
+/// arena.alloc(1);
+/// assert_eq!(arena.capacity(), 8);
+/// ```
+///
+/// With the `std` feature, chunks can also grow only while the arena is filled quickly,
+/// see [`DynArena::with_adaptive_chunks`].
+///
+/// Like [`Arena`](crate::Arena), `DynArena` is invariant in `T`:
+///
+/// ```compile_fail
+/// use linked_list_arena::dynamic::DynArena;
+///
+/// fn shorten<'a>(arena: &'a DynArena<&'static str>) -> &'a DynArena<&'a str> {
+///     arena
+/// }
+/// ```
+pub struct DynArena<T> {
+    chunks: ErasedChunks,
+    /// The arena owns its elements, and `Cell` makes it invariant in `T`.
+    _marker: PhantomData<Cell<T>>,
+}
+
+/// The chunk list of a [`DynArena`], with a type that doesn't mention `T`.
+///
+/// See `ErasedInner` in the crate root for why the elements are dropped through a
+/// function pointer.
+struct ErasedChunks {
+    /// The newest chunk, or `None` if nothing has been allocated yet.
+    head: Cell<Option<NonNull<ChunkHeader>>>,
+    /// The capacity of the next chunk that is allocated.
+    next_capacity: Cell<usize>,
+    growth: Growth,
+    /// The capacity that growing stops at.
+    max_capacity: usize,
+    /// The number of elements, including zero-sized ones, which don't need any chunks.
+    len: Cell<usize>,
+    chunk_count: Cell<usize>,
+    /// The total number of slots in all chunks.
+    capacity: Cell<usize>,
+    drop_chunks: unsafe fn(&mut ErasedChunks),
+}
+
+/// How the capacity of the next chunk is chosen.
+enum Growth {
+    /// All chunks have the same capacity.
+    Fixed,
+    /// Every chunk has twice the capacity of the previous one.
+    Doubling,
+    /// The capacity doubles whenever two chunks in a row were filled within `window` of
+    /// being allocated.
+    #[cfg(feature = "std")]
+    Adaptive {
+        window: Duration,
+        /// The number of chunks in a row that were filled within the window.
+        fast_fills: Cell<u32>,
+        /// When the head chunk was allocated.
+        head_allocated_at: Cell<Option<Instant>>,
+    },
+}
+
+/// The header at the start of every chunk. The slots of the chunk follow it.
+///
+/// The header and the slots don't overlap, so the header may be accessed while there are
+/// references to the elements.
+struct ChunkHeader {
+    /// The next (older) chunk.
+    next: Option<NonNull<ChunkHeader>>,
+    /// The number of initialized slots.
+    len: usize,
+    capacity: usize,
+}
+
+impl<T> DynArena<T> {
+    const IS_ZST: bool = core::mem::size_of::<T>() == 0;
+
+    /// Creates a new arena whose chunks all have room for `capacity` elements.
+    /// This function does not allocate any memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero or if a chunk would be larger than `isize::MAX` bytes.
+    pub fn with_chunk_capacity(capacity: usize) -> Self {
+        Self::with_growth(capacity, Growth::Fixed, capacity)
+    }
+
+    /// Creates a new arena whose first chunk has room for `initial_capacity` elements, and
+    /// where every further chunk has room for twice as many elements as the previous one.
+    /// This function does not allocate any memory.
+    ///
+    /// Chunks stop growing once their size in bytes would no longer fit in an `isize`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `initial_capacity` is zero or if a chunk would be larger than `isize::MAX`
+    /// bytes.
+    pub fn with_doubling_chunks(initial_capacity: usize) -> Self {
+        Self::with_growth(initial_capacity, Growth::Doubling, usize::MAX)
+    }
+
+    /// Creates a new arena whose first chunk has room for `initial_capacity` elements, and
+    /// where every further chunk has room for twice as many elements as the previous one,
+    /// up to `max_capacity` elements.
+    /// This function does not allocate any memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `initial_capacity` is zero, if `max_capacity` is smaller than
+    /// `initial_capacity` or if a chunk would be larger than `isize::MAX` bytes.
+    pub fn with_doubling_chunks_up_to(initial_capacity: usize, max_capacity: usize) -> Self {
+        assert!(
+            initial_capacity <= max_capacity,
+            "the maximum chunk capacity must not be smaller than the initial one"
+        );
+        Self::with_growth(initial_capacity, Growth::Doubling, max_capacity)
+    }
+
+    /// Creates a new arena whose first chunk has room for `initial_capacity` elements, and
+    /// whose chunks only grow while the arena is filled quickly.
+    /// This function does not allocate any memory.
+    ///
+    /// A chunk counts as filled quickly if it is full less than `window` after it was
+    /// allocated. Whenever this happens to two chunks in a row, the next chunk gets twice
+    /// the capacity of the previous one, up to `max_capacity` elements. Arenas that are
+    /// filled slowly keep their small chunks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `initial_capacity` is zero, if `max_capacity` is smaller than
+    /// `initial_capacity` or if a chunk would be larger than `isize::MAX` bytes.
+    #[cfg(feature = "std")]
+    pub fn with_adaptive_chunks(
+        initial_capacity: usize,
+        max_capacity: usize,
+        window: Duration,
+    ) -> Self {
+        assert!(
+            initial_capacity <= max_capacity,
+            "the maximum chunk capacity must not be smaller than the initial one"
+        );
+        let growth = Growth::Adaptive {
+            window,
+            fast_fills: Cell::new(0),
+            head_allocated_at: Cell::new(None),
+        };
+        Self::with_growth(initial_capacity, growth, max_capacity)
+    }
+
+    fn with_growth(capacity: usize, growth: Growth, max_capacity: usize) -> Self {
+        assert!(
+            capacity != 0,
+            "chunks must have room for at least one element"
+        );
+        assert!(
+            chunk_layout::<T>(capacity).is_some(),
+            "the size of a chunk in bytes must fit in an `isize`"
+        );
+        DynArena {
+            chunks: ErasedChunks {
+                head: Cell::new(None),
+                next_capacity: Cell::new(capacity),
+                growth,
+                max_capacity,
+                len: Cell::new(0),
+                chunk_count: Cell::new(0),
+                capacity: Cell::new(0),
+                drop_chunks: drop_chunks::<T>,
+            },
+            _marker: PhantomData,
+        }
+    }
+
+    /// Allocates a new element in the arena and returns a mutable reference to it.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc(&self, elem: T) -> &mut T {
+        let len = &self.chunks.len;
+        len.set(len.get().checked_add(1).expect("too many elements"));
+        if Self::IS_ZST {
+            return unsafe { write(NonNull::dangling(), elem) };
+        }
+        let head = match self.chunks.head.get() {
+            Some(head) if unsafe { (*head.as_ptr()).len < (*head.as_ptr()).capacity } => head,
+            _ => self.push_chunk(),
+        };
+        unsafe {
+            // No reference to the header is created, and the slot hasn't been handed out.
+            let index = (*head.as_ptr()).len;
+            (*head.as_ptr()).len = index + 1;
+            write(slots::<T>(head).add(index), elem)
+        }
+    }
+
+    /// Returns the number of elements in the arena.
+    pub fn len(&self) -> usize {
+        self.chunks.len.get()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of chunks the arena has allocated.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.chunk_count.get()
+    }
+
+    /// Returns the total number of slots in all chunks.
+    ///
+    /// For zero-sized types, this is `usize::MAX`.
+    pub fn capacity(&self) -> usize {
+        if Self::IS_ZST {
+            return usize::MAX;
+        }
+        self.chunks.capacity.get()
+    }
+
+    /// Returns the capacity of the next chunk the arena will allocate.
+    pub fn next_chunk_capacity(&self) -> usize {
+        self.chunks.next_capacity.get()
+    }
+
+    /// Allocates a new chunk and makes it the head chunk.
+    fn push_chunk(&self) -> NonNull<ChunkHeader> {
+        let chunks = &self.chunks;
+        #[cfg(feature = "std")]
+        if let Growth::Adaptive {
+            window,
+            fast_fills,
+            head_allocated_at,
+        } = &chunks.growth
+        {
+            // A new chunk is only needed once the head chunk is full.
+            let fast = head_allocated_at
+                .get()
+                .is_some_and(|allocated_at| allocated_at.elapsed() < *window);
+            fast_fills.set(if fast { fast_fills.get() + 1 } else { 0 });
+            if fast_fills.get() == 2 {
+                fast_fills.set(0);
+                self.grow_next_capacity();
+            }
+            head_allocated_at.set(Some(Instant::now()));
+        }
+        let capacity = chunks.next_capacity.get();
+        // The capacity was checked when it was stored.
+        let layout = chunk_layout::<T>(capacity).unwrap();
+        let Some(ptr) = NonNull::new(unsafe { alloc(layout) }) else {
+            handle_alloc_error(layout);
+        };
+        let header = ptr.cast::<ChunkHeader>();
+        unsafe {
+            header.write(ChunkHeader {
+                next: chunks.head.get(),
+                len: 0,
+                capacity,
+            })
+        };
+        chunks.head.set(Some(header));
+        chunks.chunk_count.set(chunks.chunk_count.get() + 1);
+        chunks.capacity.set(chunks.capacity.get() + capacity);
+        if let Growth::Doubling = chunks.growth {
+            self.grow_next_capacity();
+        }
+        header
+    }
+
+    /// Doubles the capacity of the next chunk, unless that exceeds the maximum capacity or
+    /// the maximum size of a chunk.
+    fn grow_next_capacity(&self) {
+        let chunks = &self.chunks;
+        let doubled = chunks
+            .next_capacity
+            .get()
+            .saturating_mul(2)
+            .min(chunks.max_capacity);
+        if chunk_layout::<T>(doubled).is_some() {
+            chunks.next_capacity.set(doubled);
+        }
+    }
+}
+
+/// Writes the element to the slot and returns a reference to it.
+///
+/// # Safety
+///
+/// The slot must be valid for writes and must not be used by anything else.
+unsafe fn write<'a, T>(mut slot: NonNull<T>, elem: T) -> &'a mut T {
+    slot.write(elem);
+    slot.as_mut()
+}
+
+/// Returns the layout of a chunk with room for `capacity` elements of type `T`, or `None` if
+/// it would be larger than `isize::MAX` bytes.
+fn chunk_layout<T>(capacity: usize) -> Option<Layout> {
+    let slots = Layout::array::<T>(capacity).ok()?;
+    Some(Layout::new::<ChunkHeader>().extend(slots).ok()?.0)
+}
+
+/// Returns a pointer to the first slot of the chunk.
+///
+/// # Safety
+///
+/// The chunk must have been allocated for elements of type `T`.
+unsafe fn slots<T>(header: NonNull<ChunkHeader>) -> NonNull<T> {
+    // The offset of the slots only depends on the alignment of `T`.
+    let (_, offset) = Layout::new::<ChunkHeader>()
+        .extend(Layout::new::<T>())
+        .unwrap();
+    header.cast::<u8>().add(offset).cast()
+}
+
+/// Drops all elements and frees all chunks of a `DynArena<T>`.
+///
+/// # Safety
+///
+/// The chunks must have been created by a `DynArena<T>`.
+unsafe fn drop_chunks<T>(chunks: &mut ErasedChunks) {
+    if core::mem::size_of::<T>() == 0 {
+        crate::drop_zsts::<T>(*chunks.len.get_mut());
+        return;
+    }
+    let mut cur_chunk = chunks.head.get();
+    while let Some(header) = cur_chunk {
+        let ChunkHeader {
+            next,
+            len,
+            capacity,
+        } = header.read();
+        let elems = core::ptr::slice_from_raw_parts_mut(slots::<T>(header).as_ptr(), len);
+        core::ptr::drop_in_place(elems);
+        dealloc(header.as_ptr().cast(), chunk_layout::<T>(capacity).unwrap());
+        cur_chunk = next;
+    }
+}
+
+impl Drop for ErasedChunks {
+    fn drop(&mut self) {
+        unsafe { (self.drop_chunks)(self) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::DynArena;
+
+    #[test]
+    fn fixed_chunk_capacity() {
+        let arena = DynArena::with_chunk_capacity(3);
+        assert!(arena.is_empty());
+        let elems: Vec<&mut i32> = (0..7).map(|i| arena.alloc(i)).collect();
+        assert_eq!(arena.len(), 7);
+        assert_eq!(arena.chunk_count(), 3);
+        assert_eq!(arena.capacity(), 9);
+        assert_eq!(arena.next_chunk_capacity(), 3);
+        assert!(elems.into_iter().map(|elem| *elem).eq(0..7));
+    }
+
+    #[test]
+    fn doubling_chunk_capacity() {
+        let arena = DynArena::with_doubling_chunks(1);
+        let elems: Vec<&mut String> = (0..7).map(|i| arena.alloc(i.to_string())).collect();
+        assert_eq!(arena.chunk_count(), 3);
+        assert_eq!(arena.capacity(), 1 + 2 + 4);
+        assert_eq!(arena.next_chunk_capacity(), 8);
+        assert!(elems
+            .iter()
+            .map(|elem| elem.parse::<i32>().unwrap())
+            .eq(0..7));
+    }
+
+    #[test]
+    fn doubling_stops_at_maximum_size() {
+        let max = isize::MAX as usize / 64;
+        let arena = DynArena::<[u8; 32]>::with_doubling_chunks(max);
+        assert_eq!(arena.next_chunk_capacity(), max);
+    }
+
+    #[test]
+    fn doubling_up_to_maximum_capacity() {
+        let arena = DynArena::with_doubling_chunks_up_to(2, 12);
+        for i in 0..30 {
+            arena.alloc(i);
+        }
+        assert_eq!(arena.chunk_count(), 5);
+        assert_eq!(arena.capacity(), 2 + 4 + 8 + 12 + 12);
+        assert_eq!(arena.next_chunk_capacity(), 12);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn adaptive_chunk_capacity() {
+        use std::time::Duration;
+
+        let arena = DynArena::with_adaptive_chunks(1, 4, Duration::from_secs(3600));
+        for i in 0..12 {
+            arena.alloc(i);
+        }
+        // Every second chunk that is filled quickly doubles the capacity.
+        assert_eq!(arena.capacity(), 1 + 1 + 2 + 2 + 4 + 4);
+        assert_eq!(arena.next_chunk_capacity(), 4);
+
+        // No chunk is filled within an empty window.
+        let arena = DynArena::with_adaptive_chunks(1, 4, Duration::ZERO);
+        for i in 0..12 {
+            arena.alloc(i);
+        }
+        assert_eq!(arena.chunk_count(), 12);
+        assert_eq!(arena.next_chunk_capacity(), 1);
+    }
+
+    #[test]
+    #[should_panic = "must not be smaller"]
+    fn maximum_below_initial_capacity() {
+        DynArena::<i32>::with_doubling_chunks_up_to(4, 2);
+    }
+
+    #[test]
+    #[should_panic = "at least one element"]
+    fn zero_chunk_capacity() {
+        DynArena::<i32>::with_chunk_capacity(0);
+    }
+
+    struct CycleParticipant<'a> {
+        other: Cell<Option<&'a CycleParticipant<'a>>>,
+    }
+
+    #[test]
+    fn cycle() {
+        let arena = DynArena::with_doubling_chunks(1);
+        let a = arena.alloc(CycleParticipant {
+            other: Cell::new(None),
+        });
+        let b = arena.alloc(CycleParticipant {
+            other: Cell::new(None),
+        });
+        a.other.set(Some(b));
+        b.other.set(Some(a));
+    }
+
+    #[test]
+    fn drop_elements() {
+        let counter = Rc::new(());
+        let arena = DynArena::with_doubling_chunks(2);
+        for _ in 0..9 {
+            arena.alloc(Rc::clone(&counter));
+        }
+        assert_eq!(Rc::strong_count(&counter), 10);
+        drop(arena);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn zero_sized_types() {
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        struct Zst;
+
+        impl Drop for Zst {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let arena = DynArena::with_chunk_capacity(2);
+        for _ in 0..5 {
+            arena.alloc(Zst);
+        }
+        assert_eq!(arena.len(), 5);
+        assert_eq!(arena.chunk_count(), 0);
+        assert_eq!(arena.capacity(), usize::MAX);
+        drop(arena);
+        assert_eq!(DROPS.load(Ordering::SeqCst), 5);
+    }
+}
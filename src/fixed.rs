@@ -0,0 +1,208 @@
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+/// An arena that allocates its elements from a buffer provided by the caller.
+///
+/// The arena never allocates memory itself, so the buffer can e.g. live on the stack or in
+/// a `static`. Once the buffer is full, [`FixedArena::try_alloc`] fails. The elements are
+/// dropped together with the arena, and like in an [`Arena`](crate::Arena), they may
+/// reference each other.
+pub struct FixedArena<'buf, T> {
+    slots: ErasedSlots,
+    /// The arena borrows the buffer and owns the elements in it. `Cell` makes it invariant
+    /// in `T`.
+    _marker: PhantomData<(&'buf mut [MaybeUninit<T>], Cell<T>)>,
+}
+
+/// The buffer of a [`FixedArena`], without `T`.
+///
+/// See `ErasedInner` in the crate root for why the elements are dropped through a
+/// function pointer.
+struct ErasedSlots {
+    start: NonNull<()>,
+    /// The number of initialized elements at the start of the buffer.
+    len: Cell<usize>,
+    capacity: usize,
+    drop_elems: unsafe fn(&mut ErasedSlots),
+}
+
+impl<'buf, T> FixedArena<'buf, T> {
+    /// Creates a new arena that allocates its elements from the buffer.
+    ///
+    /// The buffer is borrowed until the arena is dropped. Its previous contents are
+    /// overwritten without being dropped.
+    pub fn new(buf: &'buf mut [MaybeUninit<T>]) -> Self {
+        FixedArena {
+            slots: ErasedSlots {
+                capacity: buf.len(),
+                start: NonNull::from(buf).cast(),
+                len: Cell::new(0),
+                drop_elems: drop_elems::<T>,
+            },
+            _marker: PhantomData,
+        }
+    }
+
+    /// Allocates a new element in the arena and returns a mutable reference to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is full.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc(&self, elem: T) -> &mut T {
+        match self.try_alloc(elem) {
+            Ok(elem) => elem,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    /// Allocates a new element in the arena and returns a mutable reference to it, or
+    /// returns the element in the error if the buffer is full.
+    #[allow(clippy::mut_from_ref)]
+    pub fn try_alloc(&self, elem: T) -> Result<&mut T, BufferFull<T>> {
+        let len = self.slots.len.get();
+        if len == self.slots.capacity {
+            return Err(BufferFull(elem));
+        }
+        self.slots.len.set(len + 1);
+        unsafe {
+            let mut slot = self.slots.start.cast::<T>().add(len);
+            slot.write(elem);
+            Ok(slot.as_mut())
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len.get()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of elements that fit into the buffer.
+    pub fn capacity(&self) -> usize {
+        self.slots.capacity
+    }
+
+    /// Returns mutable references to all elements, in allocation order.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { core::slice::from_raw_parts_mut(self.slots.start.cast().as_ptr(), self.len()) }
+    }
+}
+
+/// The error returned by [`FixedArena::try_alloc`] if the buffer is full.
+///
+/// It holds the value that was passed in, so that it isn't lost.
+pub struct BufferFull<T>(pub T);
+
+impl<T> BufferFull<T> {
+    /// Returns the value that couldn't be allocated.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Debug for BufferFull<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BufferFull { .. }")
+    }
+}
+
+impl<T> fmt::Display for BufferFull<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the buffer of the arena is full")
+    }
+}
+
+impl<T> core::error::Error for BufferFull<T> {}
+
+/// Drops the initialized elements of the buffer.
+///
+/// # Safety
+///
+/// The buffer must have been created by a `FixedArena<T>`.
+unsafe fn drop_elems<T>(slots: &mut ErasedSlots) {
+    let elems = NonNull::slice_from_raw_parts(slots.start.cast::<T>(), slots.len.take());
+    core::ptr::drop_in_place(elems.as_ptr());
+}
+
+impl Drop for ErasedSlots {
+    fn drop(&mut self) {
+        unsafe { (self.drop_elems)(self) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn alloc_until_full() {
+        let mut buf = [const { MaybeUninit::uninit() }; 3];
+        let mut arena = FixedArena::new(&mut buf);
+        assert!(arena.is_empty());
+        let first = arena.alloc(String::from("a"));
+        arena.alloc(String::from("b"));
+        first.push('!');
+        assert!(arena.try_alloc(String::from("c")).is_ok());
+        let err = arena.try_alloc(String::from("d")).unwrap_err();
+        assert_eq!(err.to_string(), "the buffer of the arena is full");
+        assert_eq!(err.into_inner(), "d");
+        assert_eq!((arena.len(), arena.capacity()), (3, 3));
+        assert_eq!(arena.as_mut_slice(), ["a!", "b", "c"]);
+    }
+
+    #[test]
+    fn drop_elements() {
+        let counter = Rc::new(());
+        let mut buf = [const { MaybeUninit::uninit() }; 8];
+        {
+            let arena = FixedArena::new(&mut buf);
+            for _ in 0..5 {
+                arena.alloc(Rc::clone(&counter));
+            }
+            assert_eq!(Rc::strong_count(&counter), 6);
+        }
+        assert_eq!(Rc::strong_count(&counter), 1);
+        // The buffer can be reused once the arena is gone.
+        let arena = FixedArena::new(&mut buf);
+        arena.alloc(Rc::clone(&counter));
+    }
+
+    #[test]
+    fn cyclic_references() {
+        struct Node<'a> {
+            next: Cell<Option<&'a Node<'a>>>,
+            name: String,
+        }
+
+        let mut buf = [const { MaybeUninit::uninit() }; 2];
+        let arena = FixedArena::new(&mut buf);
+        let a = arena.alloc(Node {
+            next: Cell::new(None),
+            name: String::from("a"),
+        });
+        let b = arena.alloc(Node {
+            next: Cell::new(Some(a)),
+            name: String::from("b"),
+        });
+        a.next.set(Some(b));
+        assert_eq!(a.next.get().unwrap().next.get().unwrap().name, "a");
+    }
+
+    #[test]
+    fn zero_sized_types() {
+        let mut buf = [MaybeUninit::uninit(); 2];
+        let arena = FixedArena::new(&mut buf);
+        arena.alloc(());
+        arena.alloc(());
+        assert!(arena.try_alloc(()).is_err());
+    }
+}
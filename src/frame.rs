@@ -0,0 +1,179 @@
+use alloc::vec::Vec;
+use core::ptr::NonNull;
+
+use crate::Arena;
+
+/// A double-buffered arena for per-frame allocations.
+///
+/// New elements are allocated into the current frame. [`FrameArena::flip`] turns the
+/// current frame into the previous frame, which stays readable while the next frame is
+/// being built, and clears the frame before it. Its chunks are kept for the next frame, so
+/// after a few frames, no more memory is allocated.
+pub struct FrameArena<const N: usize, T> {
+    current: Arena<N, T>,
+    /// Nothing is allocated in the previous frame, so its elements can be read through
+    /// shared references.
+    previous: Arena<N, T>,
+    /// The initialized part of every chunk of the previous frame, in allocation order.
+    previous_chunks: Vec<NonNull<[T]>>,
+}
+
+impl<const N: usize, T> FrameArena<N, T> {
+    /// Creates a new frame arena with an empty current and previous frame.
+    /// This function does not allocate any memory.
+    pub fn new() -> Self {
+        FrameArena {
+            current: Arena::new(),
+            previous: Arena::new(),
+            previous_chunks: Vec::new(),
+        }
+    }
+
+    /// Allocates a new element in the current frame and returns a mutable reference to it.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc(&self, elem: T) -> &mut T {
+        self.current.alloc(elem)
+    }
+
+    /// Returns the elements of the previous frame.
+    pub fn previous(&self) -> PreviousFrame<'_, T> {
+        PreviousFrame {
+            chunks: &self.previous_chunks,
+        }
+    }
+
+    /// Ends the current frame.
+    ///
+    /// The current frame becomes the previous frame. The old previous frame is cleared,
+    /// which drops its elements, and its chunks are reused by the next frame.
+    pub fn flip(&mut self) {
+        // Forget the slices first, in case a destructor panics.
+        self.previous_chunks.clear();
+        self.previous.clear();
+        core::mem::swap(&mut self.current, &mut self.previous);
+        self.previous.chunk_slices_into(&mut self.previous_chunks);
+    }
+}
+
+impl<const N: usize, T> Default for FrameArena<N, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The elements of the previous frame of a [`FrameArena`], created by
+/// [`FrameArena::previous`].
+pub struct PreviousFrame<'a, T> {
+    chunks: &'a [NonNull<[T]>],
+}
+
+impl<'a, T> PreviousFrame<'a, T> {
+    /// Returns the number of elements in the frame.
+    pub fn len(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over the elements of the frame, in allocation order.
+    pub fn iter(&self) -> impl Iterator<Item = &'a T> + 'a {
+        // The frame arena is borrowed, so the previous frame is neither cleared nor
+        // handed out mutably.
+        self.chunks
+            .iter()
+            .flat_map(|chunk| unsafe { chunk.as_ref() })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn read_previous_frame_while_building() {
+        let mut frames = FrameArena::<4, i32>::new();
+        assert!(frames.previous().is_empty());
+        for i in 0..5 {
+            frames.alloc(i);
+        }
+        frames.flip();
+
+        for &x in frames.previous().iter() {
+            frames.alloc(x * 2);
+        }
+        assert!(frames.previous().iter().copied().eq(0..5));
+        frames.flip();
+        assert!(frames.previous().iter().copied().eq((0..5).map(|x| x * 2)));
+        frames.flip();
+        assert!(frames.previous().is_empty());
+    }
+
+    struct WithDrop(Arc<AtomicUsize>);
+
+    impl Drop for WithDrop {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn reuse_chunks() {
+        let mut frames = FrameArena::<3, u32>::new();
+        let mut firsts = Vec::new();
+        for frame in 0..4 {
+            firsts.push(frames.alloc(frame) as *const u32);
+            for i in 0..2 {
+                frames.alloc(i);
+            }
+            frames.flip();
+            assert_eq!(frames.previous().len(), 3);
+        }
+        // Every other frame uses the same chunks.
+        assert_eq!((firsts[0], firsts[1]), (firsts[2], firsts[3]));
+        assert_ne!(firsts[0], firsts[1]);
+        assert_eq!(frames.current.chunk_count(), 1);
+        assert_eq!(frames.previous.chunk_count(), 1);
+    }
+
+    #[test]
+    fn reuse_chunk_list() {
+        let mut frames = FrameArena::<2, u32>::new();
+        for i in 0..5 {
+            frames.alloc(i);
+        }
+        frames.flip();
+        let chunks = frames.previous_chunks.as_ptr();
+        for frame in 0..4 {
+            for i in 0..frame {
+                frames.alloc(i);
+            }
+            frames.flip();
+            // The list of chunks of the previous frame keeps its buffer.
+            assert_eq!(frames.previous_chunks.as_ptr(), chunks);
+        }
+    }
+
+    #[test]
+    fn drop_frames() {
+        let drop_counter = Arc::new(AtomicUsize::new(0));
+
+        let mut frames = FrameArena::<3, WithDrop>::new();
+        for _ in 0..4 {
+            frames.alloc(WithDrop(Arc::clone(&drop_counter)));
+        }
+        frames.flip();
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 0);
+        frames.alloc(WithDrop(Arc::clone(&drop_counter)));
+        frames.flip();
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 4);
+        frames.alloc(WithDrop(Arc::clone(&drop_counter)));
+        drop(frames);
+
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 6);
+    }
+}
@@ -0,0 +1,506 @@
+use alloc::alloc::handle_alloc_error;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Index, Range};
+use core::ptr::NonNull;
+use core::slice;
+
+use crate::{Allocator, Arena, Chunk, Global, InnerArena, Link};
+
+/// A read-only arena, created by [`Arena::freeze`](crate::Arena::freeze).
+///
+/// Since no more elements can be allocated, the elements can be accessed through shared
+/// references without any interior mutability. This also means that a `FrozenArena` is
+/// `Send` and `Sync` whenever `T` is.
+pub struct FrozenArena<const N: usize, T, A: Allocator = Global> {
+    /// The chunks in allocation order. Always empty if `T` is zero-sized.
+    chunks: Vec<Link<N, T>>,
+    /// For every chunk, the number of initialized elements in it and all chunks before it.
+    ///
+    /// Zero-sized elements are counted as if they were in a single chunk.
+    ends: Vec<usize>,
+    /// The allocator that the chunks are freed with.
+    alloc: A,
+}
+
+// The elements can only be reached through shared references, like in a `Box<[T], A>`.
+unsafe impl<const N: usize, T: Send, A: Allocator + Send> Send for FrozenArena<N, T, A> {}
+unsafe impl<const N: usize, T: Sync, A: Allocator + Sync> Sync for FrozenArena<N, T, A> {}
+
+impl<const N: usize, T, A: Allocator> FrozenArena<N, T, A> {
+    pub(crate) fn from_inner(inner: Option<InnerArena<N, T>>, zst_len: usize, alloc: A) -> Self {
+        let Some(arena) = inner else {
+            return FrozenArena {
+                chunks: Vec::new(),
+                ends: if zst_len > 0 {
+                    vec![zst_len]
+                } else {
+                    Vec::new()
+                },
+                alloc,
+            };
+        };
+        let chunks_and_lens = arena.unlink(&alloc);
+
+        let mut chunks = Vec::with_capacity(chunks_and_lens.len());
+        let mut ends = Vec::with_capacity(chunks_and_lens.len());
+        let mut end = 0;
+        for (chunk, len) in chunks_and_lens.into_iter().rev() {
+            end += len;
+            chunks.push(chunk);
+            ends.push(end);
+        }
+        FrozenArena {
+            chunks,
+            ends,
+            alloc,
+        }
+    }
+
+    /// Returns the number of elements in the arena.
+    pub fn len(&self) -> usize {
+        self.ends.last().copied().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a reference to the element with the given index, where the index counts
+    /// the elements in allocation order.
+    ///
+    /// This takes `O(log(chunks))` time.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let chunk_index = self.ends.partition_point(|&end| end <= index);
+        if chunk_index == self.ends.len() {
+            return None;
+        }
+        // All slots before the end of the chunk have been initialized.
+        let ptr = self.chunk_ptr(chunk_index);
+        Some(unsafe { ptr.add(index - self.chunk_start(chunk_index)).as_ref() })
+    }
+
+    /// Returns an iterator over all elements in allocation order.
+    pub fn iter(&self) -> Iter<'_, N, T, A> {
+        Iter {
+            arena: self,
+            chunks: 0..self.ends.len(),
+            elems: [].iter(),
+        }
+    }
+
+    /// Returns an iterator over the chunks in allocation order, where each chunk is given
+    /// as the slice of its initialized elements.
+    ///
+    /// Slices are shorter than `N` if [`Arena::alloc_extend`](crate::Arena::alloc_extend)
+    /// skipped the rest of the chunk, and the last slice may be shorter. Slices of the
+    /// chunks that `alloc_extend` allocated for more than `N` elements are longer.
+    pub fn as_chunk_slices(&self) -> impl Iterator<Item = &[T]> + '_ {
+        (0..self.ends.len()).map(|i| self.chunk_slice(i))
+    }
+
+    /// Returns the index of the first element (in allocation order) that satisfies the
+    /// predicate.
+    pub fn position(&self, mut predicate: impl FnMut(&T) -> bool) -> Option<usize> {
+        self.as_chunk_slices().enumerate().find_map(|(i, chunk)| {
+            let j = chunk.iter().position(&mut predicate)?;
+            Some(self.chunk_start(i) + j)
+        })
+    }
+
+    /// Returns the first element (in allocation order) that satisfies the predicate.
+    pub fn find(&self, mut predicate: impl FnMut(&T) -> bool) -> Option<&T> {
+        self.as_chunk_slices()
+            .find_map(|chunk| chunk.iter().find(|elem| predicate(elem)))
+    }
+
+    /// Returns the initialized part of the chunk with the given index as a slice.
+    fn chunk_slice(&self, chunk_index: usize) -> &[T] {
+        let ptr = self.chunk_ptr(chunk_index);
+        unsafe { slice::from_raw_parts(ptr.as_ptr(), self.chunk_len(chunk_index)) }
+    }
+
+    /// Returns a pointer to the first slot of the chunk with the given index.
+    fn chunk_ptr(&self, chunk_index: usize) -> NonNull<T> {
+        match self.chunks.get(chunk_index) {
+            Some(chunk) => unsafe {
+                NonNull::new_unchecked(core::ptr::addr_of_mut!((*chunk.as_ptr()).slots)).cast()
+            },
+            // Zero-sized elements don't live in a chunk.
+            None => NonNull::dangling(),
+        }
+    }
+
+    /// Returns the index of the first element in the chunk with the given index.
+    fn chunk_start(&self, chunk_index: usize) -> usize {
+        match chunk_index {
+            0 => 0,
+            i => self.ends[i - 1],
+        }
+    }
+
+    /// Returns the number of initialized elements in the chunk with the given index.
+    fn chunk_len(&self, chunk_index: usize) -> usize {
+        self.ends[chunk_index] - self.chunk_start(chunk_index)
+    }
+}
+
+impl<const N: usize, T, A: Allocator> Index<usize> for FrozenArena<N, T, A> {
+    type Output = T;
+
+    /// Returns the element with the given index, see [`FrozenArena::get`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    fn index(&self, index: usize) -> &T {
+        match self.get(index) {
+            Some(elem) => elem,
+            None => panic!(
+                "index out of bounds: the len is {} but the index is {index}",
+                self.len()
+            ),
+        }
+    }
+}
+
+impl<'a, const N: usize, T, A: Allocator> IntoIterator for &'a FrozenArena<N, T, A> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, N, T, A>;
+
+    fn into_iter(self) -> Iter<'a, N, T, A> {
+        self.iter()
+    }
+}
+
+/// An iterator over the elements of a [`FrozenArena`] in allocation order, created by
+/// [`FrozenArena::iter`].
+pub struct Iter<'a, const N: usize, T, A: Allocator = Global> {
+    arena: &'a FrozenArena<N, T, A>,
+    /// The indices of the chunks that haven't been visited yet.
+    chunks: Range<usize>,
+    /// The remaining elements of the current chunk.
+    elems: slice::Iter<'a, T>,
+}
+
+impl<'a, const N: usize, T, A: Allocator> Iterator for Iter<'a, N, T, A> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            if let Some(elem) = self.elems.next() {
+                return Some(elem);
+            }
+            self.elems = self.arena.chunk_slice(self.chunks.next()?).iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let later = if self.chunks.is_empty() {
+            0
+        } else {
+            self.arena.len() - self.arena.chunk_start(self.chunks.start)
+        };
+        let len = self.elems.len() + later;
+        (len, Some(len))
+    }
+}
+
+impl<const N: usize, T, A: Allocator> ExactSizeIterator for Iter<'_, N, T, A> {}
+
+impl<const N: usize, T: fmt::Debug, A: Allocator> fmt::Debug for FrozenArena<N, T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<const N: usize, T, A: Allocator + Default> FromIterator<T> for FrozenArena<N, T, A> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter().collect::<Arena<N, T, A>>().freeze()
+    }
+}
+
+impl<const N: usize, T: Clone, A: Allocator + Clone> Clone for FrozenArena<N, T, A> {
+    /// Clones the elements into new chunks, with the same number of elements in each chunk.
+    fn clone(&self) -> Self {
+        let mut copy = FrozenArena {
+            chunks: Vec::with_capacity(self.chunks.len()),
+            ends: Vec::with_capacity(self.ends.len()),
+            alloc: self.alloc.clone(),
+        };
+        for i in 0..self.ends.len() {
+            if !self.chunks.is_empty() {
+                // Chunks with more than `N` elements are copied into an oversized chunk.
+                let capacity = self.chunk_len(i).max(N);
+                let chunk = Chunk::try_with_capacity_in(capacity, &copy.alloc)
+                    .unwrap_or_else(|| handle_alloc_error(Chunk::<N, T>::layout(capacity)));
+                copy.chunks.push(chunk);
+            }
+            copy.ends.push(copy.len());
+            for (j, elem) in self.chunk_slice(i).iter().enumerate() {
+                let elem = elem.clone();
+                // The copy only counts the elements that have been written, so it can be
+                // dropped if `clone` panics.
+                unsafe { copy.chunk_ptr(i).add(j).write(elem) };
+                *copy.ends.last_mut().unwrap() += 1;
+            }
+        }
+        copy
+    }
+}
+
+impl<const N: usize, T, A: Allocator> Drop for FrozenArena<N, T, A> {
+    fn drop(&mut self) {
+        // The elements are dropped in reverse allocation order, like in `Arena::destroy`.
+        if core::mem::needs_drop::<T>() {
+            for i in (0..self.ends.len()).rev() {
+                let ptr = self.chunk_ptr(i);
+                for j in (0..self.chunk_len(i)).rev() {
+                    unsafe { ptr.add(j).drop_in_place() };
+                }
+            }
+        }
+        for &chunk in &self.chunks {
+            unsafe { Chunk::free(chunk, &self.alloc) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::FrozenArena;
+    use crate::Arena;
+
+    #[test]
+    fn freeze_empty_arena() {
+        let frozen = Arena::<3, i32>::new().freeze();
+        assert!(frozen.is_empty());
+        assert_eq!(frozen.len(), 0);
+        assert_eq!(frozen.get(0), None);
+        assert_eq!(frozen.iter().next(), None);
+    }
+
+    #[test]
+    fn get_and_iter() {
+        let arena = Arena::<3, i32>::new();
+        for i in 0..7 {
+            arena.alloc(i);
+        }
+        let frozen = arena.freeze();
+        assert_eq!(frozen.len(), 7);
+        assert_eq!(frozen.get(0), Some(&0));
+        assert_eq!(frozen.get(3), Some(&3));
+        assert_eq!(frozen.get(6), Some(&6));
+        assert_eq!(frozen.get(7), None);
+        assert!(frozen.iter().copied().eq(0..7));
+    }
+
+    #[test]
+    fn index_and_into_iter() {
+        let arena = Arena::<3, String>::new();
+        for i in 0..7 {
+            arena.alloc(i.to_string());
+        }
+        let frozen = arena.freeze();
+        assert_eq!(frozen[4], "4");
+        let mut iter = frozen.iter();
+        iter.nth(3);
+        assert_eq!(iter.len(), 3);
+        let mut all = Vec::new();
+        for s in &frozen {
+            all.push(s.as_str());
+        }
+        assert_eq!(all, ["0", "1", "2", "3", "4", "5", "6"]);
+    }
+
+    #[test]
+    #[should_panic = "index out of bounds: the len is 2 but the index is 2"]
+    fn index_out_of_bounds() {
+        let frozen: FrozenArena<4, i32> = [1, 2].into_iter().collect();
+        let _ = frozen[2];
+    }
+
+    #[test]
+    fn full_head_chunk() {
+        let arena = Arena::<3, i32>::new();
+        for i in 0..6 {
+            arena.alloc(i);
+        }
+        let frozen = arena.freeze();
+        assert_eq!(frozen.len(), 6);
+        assert!(frozen.iter().copied().eq(0..6));
+    }
+
+    #[test]
+    fn share_between_threads() {
+        let arena = Arena::<4, String>::new();
+        for i in 0..10 {
+            arena.alloc(i.to_string());
+        }
+        let frozen = Arc::new(arena.freeze());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let frozen = Arc::clone(&frozen);
+                std::thread::spawn(move || frozen.iter().map(|s| s.len()).sum::<usize>())
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 10);
+        }
+    }
+
+    #[test]
+    fn chunk_slices() {
+        let arena = Arena::<3, i32>::new();
+        for i in 0..7 {
+            arena.alloc(i);
+        }
+        let frozen = arena.freeze();
+        let slices: Vec<&[i32]> = frozen.as_chunk_slices().collect();
+        assert_eq!(slices, [&[0, 1, 2][..], &[3, 4, 5], &[6]]);
+    }
+
+    #[test]
+    fn position_and_find() {
+        let arena = Arena::<3, i32>::new();
+        for i in 0..7 {
+            arena.alloc(i * 10);
+        }
+        let frozen = arena.freeze();
+        assert_eq!(frozen.position(|&x| x == 0), Some(0));
+        assert_eq!(frozen.position(|&x| x > 35), Some(4));
+        assert_eq!(frozen.position(|&x| x == 60), Some(6));
+        assert_eq!(frozen.position(|&x| x == 5), None);
+        assert_eq!(frozen.find(|&x| x > 35), Some(&40));
+        assert_eq!(frozen.find(|&x| x > 60), None);
+    }
+
+    #[test]
+    fn covariant() {
+        fn shorten<'a>(frozen: FrozenArena<4, &'static str>) -> FrozenArena<4, &'a str> {
+            frozen
+        }
+        let arena = Arena::<4, &'static str>::new();
+        arena.alloc("a");
+        assert_eq!(shorten(arena.freeze()).get(0), Some(&"a"));
+    }
+
+    #[test]
+    fn send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<FrozenArena<8, i32>>();
+        assert_send_sync::<FrozenArena<8, String>>();
+    }
+
+    #[test]
+    fn read_chunk_slices_concurrently() {
+        let arena = Arena::<16, u64>::new();
+        for i in 0..1000 {
+            arena.alloc(i);
+        }
+        let frozen = arena.freeze();
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let sum: u64 = frozen
+                        .as_chunk_slices()
+                        .map(|c| c.iter().sum::<u64>())
+                        .sum();
+                    assert_eq!(sum, 999 * 1000 / 2);
+                });
+            }
+        });
+    }
+
+    struct WithDrop(Arc<AtomicUsize>);
+
+    impl Drop for WithDrop {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn freeze_zero_sized() {
+        let arena = Arena::<3, ()>::new();
+        for _ in 0..5 {
+            arena.alloc(());
+        }
+        let frozen = arena.freeze();
+        assert_eq!(frozen.len(), 5);
+        assert_eq!(frozen.get(4), Some(&()));
+        assert_eq!(frozen.get(5), None);
+        assert_eq!(frozen.iter().count(), 5);
+        assert_eq!(frozen.as_chunk_slices().count(), 1);
+    }
+
+    #[test]
+    fn debug() {
+        let arena = Arena::<2, i32>::new();
+        for i in 0..3 {
+            arena.alloc(i);
+        }
+        assert_eq!(format!("{:?}", arena.freeze()), "[0, 1, 2]");
+    }
+
+    #[test]
+    fn clone() {
+        let arena = Arena::<3, String>::new();
+        for i in 0..7 {
+            arena.alloc(i.to_string());
+        }
+        let frozen = arena.freeze();
+        let copy = frozen.clone();
+        drop(frozen);
+        assert!(copy
+            .iter()
+            .map(String::as_str)
+            .eq(["0", "1", "2", "3", "4", "5", "6"]));
+        assert_eq!(copy.as_chunk_slices().count(), 3);
+
+        let arena = Arena::<3, ()>::new();
+        arena.alloc(());
+        assert_eq!(arena.freeze().clone().len(), 1);
+    }
+
+    #[test]
+    fn drop_in_reverse_order() {
+        struct Logged<'a>(usize, &'a RefCell<Vec<usize>>);
+
+        impl Drop for Logged<'_> {
+            fn drop(&mut self) {
+                self.1.borrow_mut().push(self.0);
+            }
+        }
+
+        let log = RefCell::new(Vec::new());
+        let arena = Arena::<3, Logged>::new();
+        arena.alloc(Logged(0, &log));
+        arena.alloc_extend([1, 2, 3].map(|i| Logged(i, &log)));
+        for i in 4..8 {
+            arena.alloc(Logged(i, &log));
+        }
+        drop(arena.freeze());
+        assert_eq!(*log.borrow(), [7, 6, 5, 4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn drop_frozen_arena() {
+        let drop_counter = Arc::new(AtomicUsize::new(0));
+
+        let arena = Arena::<3, WithDrop>::new();
+        for _ in 0..5 {
+            arena.alloc(WithDrop(Arc::clone(&drop_counter)));
+        }
+        let frozen = arena.freeze();
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 0);
+        drop(frozen);
+
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 5);
+    }
+}
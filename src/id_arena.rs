@@ -0,0 +1,347 @@
+//! A wrapper with the API of the `id_arena` crate, backed by an [`IndexArena`].
+//!
+//! Code written against `id_arena::{Arena, Id}` can switch to this module by changing its
+//! imports, unless it uses the `ArenaBehavior` parameter of `id_arena` for custom id types.
+//! The elements are stored in chunks of [`CHUNK_LEN`] slots and never move.
+
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use core::ops::{Index, IndexMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::index::{self, IndexArena};
+
+/// The number of slots in each chunk.
+///
+/// There is no const parameter for the chunk length, because `Arena::new()` couldn't infer
+/// it.
+pub const CHUNK_LEN: usize = 64;
+
+/// The id of the next arena that is created.
+static NEXT_ARENA_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// An arena that hands out [`Id`]s, like `id_arena::Arena`.
+pub struct Arena<T> {
+    inner: IndexArena<CHUNK_LEN, T>,
+    /// Distinguishes the ids of this arena from the ids of other arenas.
+    arena_id: usize,
+}
+
+/// The id of an element in an [`Arena`]. It is the position of the element in allocation
+/// order, together with the arena it belongs to.
+pub struct Id<T> {
+    index: usize,
+    arena_id: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Id<T> {
+    /// Returns the position of the element in allocation order.
+    pub fn index(self) -> usize {
+        self.index
+    }
+}
+
+impl<T> Arena<T> {
+    /// Creates a new arena.
+    /// This function does not allocate any memory.
+    pub fn new() -> Self {
+        Self::from_inner(IndexArena::new())
+    }
+
+    /// Creates a new arena with room for at least `capacity` elements.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::from_inner(IndexArena::with_capacity(capacity))
+    }
+
+    fn from_inner(inner: IndexArena<CHUNK_LEN, T>) -> Self {
+        Arena {
+            inner,
+            arena_id: NEXT_ARENA_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Allocates a new element in the arena and returns its id.
+    pub fn alloc(&mut self, item: T) -> Id<T> {
+        let id = self.next_id();
+        self.inner.alloc(item);
+        id
+    }
+
+    /// Allocates a new element that is created from its own id.
+    pub fn alloc_with_id(&mut self, f: impl FnOnce(Id<T>) -> T) -> Id<T> {
+        let id = self.next_id();
+        self.alloc(f(id))
+    }
+
+    /// Returns the id that the next allocated element will get.
+    pub fn next_id(&self) -> Id<T> {
+        self.id_at(self.inner.len())
+    }
+
+    /// Returns a reference to the element with the given id, or `None` if the id belongs
+    /// to another arena.
+    pub fn get(&self, id: Id<T>) -> Option<&T> {
+        self.owns(id)
+            .then(|| self.inner.get(self.inner.id_at(id.index)))
+    }
+
+    /// Returns a mutable reference to the element with the given id, or `None` if the id
+    /// belongs to another arena.
+    pub fn get_mut(&mut self, id: Id<T>) -> Option<&mut T> {
+        if self.owns(id) {
+            let id = self.inner.id_at(id.index);
+            Some(self.inner.get_mut(id))
+        } else {
+            None
+        }
+    }
+
+    /// Returns an iterator over the ids and elements of the arena, in allocation order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            inner: self.inner.iter(),
+            arena_id: self.arena_id,
+            index: 0,
+        }
+    }
+
+    /// Returns an iterator over the ids and mutable references to the elements of the
+    /// arena, in allocation order.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            inner: self.inner.iter_mut(),
+            arena_id: self.arena_id,
+            index: 0,
+        }
+    }
+
+    /// Returns the number of elements in the arena.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns whether the id was handed out by this arena.
+    fn owns(&self, id: Id<T>) -> bool {
+        id.arena_id == self.arena_id && id.index < self.len()
+    }
+
+    fn id_at(&self, index: usize) -> Id<T> {
+        Id {
+            index,
+            arena_id: self.arena_id,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Index<Id<T>> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, id: Id<T>) -> &T {
+        self.get(id).expect("the id belongs to another arena")
+    }
+}
+
+impl<T> IndexMut<Id<T>> for Arena<T> {
+    fn index_mut(&mut self, id: Id<T>) -> &mut T {
+        self.get_mut(id).expect("the id belongs to another arena")
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Arena<T> {
+    type Item = (Id<T>, &'a T);
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut Arena<T> {
+    type Item = (Id<T>, &'a mut T);
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T> IntoIterator for Arena<T> {
+    type Item = T;
+    type IntoIter = index::IntoIter<CHUNK_LEN, T>;
+
+    /// Returns an iterator that moves the elements out of the arena, in allocation order.
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.into_iter()
+    }
+}
+
+/// An iterator over the ids and elements of an [`Arena`], created by [`Arena::iter`].
+pub struct Iter<'a, T> {
+    inner: index::Iter<'a, CHUNK_LEN, T>,
+    arena_id: usize,
+    index: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (Id<T>, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (_, elem) = self.inner.next()?;
+        let id = Id {
+            index: self.index,
+            arena_id: self.arena_id,
+            _marker: PhantomData,
+        };
+        self.index += 1;
+        Some((id, elem))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// An iterator over the ids and mutable references to the elements of an [`Arena`],
+/// created by [`Arena::iter_mut`].
+pub struct IterMut<'a, T> {
+    inner: index::IterMut<'a, CHUNK_LEN, T>,
+    arena_id: usize,
+    index: usize,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = (Id<T>, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (_, elem) = self.inner.next()?;
+        let id = Id {
+            index: self.index,
+            arena_id: self.arena_id,
+            _marker: PhantomData,
+        };
+        self.index += 1;
+        Some((id, elem))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+// Like for `ArenaId`, the trait implementations of `Id` don't need any bounds on `T`.
+
+impl<T> Clone for Id<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Id<T> {}
+
+impl<T> PartialEq for Id<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.index, self.arena_id) == (other.index, other.arena_id)
+    }
+}
+
+impl<T> Eq for Id<T> {}
+
+impl<T> PartialOrd for Id<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Id<T> {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        (self.index, self.arena_id).cmp(&(other.index, other.arena_id))
+    }
+}
+
+impl<T> Hash for Id<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.index, self.arena_id).hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Id<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Id")
+            .field("index", &self.index)
+            .field("arena_id", &self.arena_id)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Arena, Id, CHUNK_LEN};
+
+    #[test]
+    fn alloc_get_and_iter() {
+        let mut arena = Arena::new();
+        assert!(arena.is_empty());
+        let ids: Vec<Id<String>> = (0..100).map(|i| arena.alloc(i.to_string())).collect();
+        assert_eq!(arena.len(), 100);
+        assert_eq!(ids[CHUNK_LEN + 3].index(), CHUNK_LEN + 3);
+        assert_eq!(arena.get(ids[1]).map(String::as_str), Some("1"));
+        arena[ids[99]].push('!');
+        assert!(arena
+            .iter()
+            .map(|(id, elem)| (id.index(), elem.as_str()))
+            .skip(97)
+            .eq([(97, "97"), (98, "98"), (99, "99!")]));
+    }
+
+    #[test]
+    fn iter_mut_and_into_iter() {
+        let mut arena = Arena::with_capacity(3);
+        let ids: Vec<_> = (0..5).map(|i| arena.alloc(i)).collect();
+        for (id, elem) in &mut arena {
+            *elem += id.index() * 10;
+        }
+        assert!((&arena).into_iter().map(|(id, _)| id).eq(ids));
+        assert!(arena.into_iter().eq([0, 11, 22, 33, 44]));
+    }
+
+    #[test]
+    fn id_from_another_arena() {
+        let mut arena = Arena::<i32>::new();
+        let mut other = Arena::<i32>::new();
+        let id = other.alloc(1);
+        other.alloc(2);
+        arena.alloc(3);
+        assert_ne!(id, arena.next_id());
+        assert_eq!(arena.get(id), None);
+        assert_eq!(arena.get_mut(id), None);
+        assert_eq!(other.get(id), Some(&1));
+    }
+
+    #[test]
+    fn alloc_with_id() {
+        struct Node {
+            this: Id<Node>,
+        }
+
+        let mut arena = Arena::<Node>::default();
+        arena.alloc(Node {
+            this: arena.next_id(),
+        });
+        let id = arena.alloc_with_id(|this| Node { this });
+        assert_eq!(arena[id].this, id);
+        assert!(arena.iter().all(|(id, node)| node.this == id));
+    }
+}
@@ -0,0 +1,544 @@
+use alloc::alloc::{alloc, dealloc, handle_alloc_error};
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::cell::{Cell, RefCell};
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ops::{Index, IndexMut};
+use core::ptr::NonNull;
+
+/// An arena that hands out [`ArenaId`]s instead of references.
+///
+/// Ids are small and `Copy`, so elements can refer to each other without lifetimes. An
+/// element is looked up in constant time through a table of chunk pointers. Since
+/// allocating doesn't return a reference, elements can be read through a shared reference
+/// while the arena grows.
+///
+/// `IndexArena` is invariant in `T`, because elements can be allocated through a shared
+/// reference:
+///
+/// ```compile_fail
+/// use linked_list_arena::index::IndexArena;
+///
+/// fn shorten<'a>(arena: &'a IndexArena<4, &'static str>) -> &'a IndexArena<4, &'a str> {
+///     arena
+/// }
+/// ```
+pub struct IndexArena<const N: usize, T> {
+    /// The chunks in allocation order. Chunks are never moved, only the table grows.
+    ///
+    /// `RefCell` also makes the arena invariant in `T`.
+    chunks: RefCell<Vec<NonNull<[MaybeUninit<T>; N]>>>,
+    /// The number of elements. All slots before it are initialized.
+    len: Cell<usize>,
+    /// The arena owns its elements.
+    _marker: PhantomData<T>,
+}
+
+/// The id of an element in an [`IndexArena`], made up of the index of its chunk and the
+/// index of its slot in that chunk.
+pub struct ArenaId<T> {
+    chunk: u32,
+    slot: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<const N: usize, T> IndexArena<N, T> {
+    const IS_ZST: bool = core::mem::size_of::<T>() == 0;
+
+    /// Creates a new arena.
+    /// This function does not allocate any memory.
+    ///
+    /// ```compile_fail
+    /// let arena = linked_list_arena::index::IndexArena::<0, i32>::new();
+    /// ```
+    pub fn new() -> Self {
+        const { crate::check_chunk_size::<N, T>() };
+        const {
+            assert!(
+                N <= u32::MAX as usize,
+                "chunks must have at most `u32::MAX` slots"
+            )
+        };
+        IndexArena {
+            chunks: RefCell::new(Vec::new()),
+            len: Cell::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a new arena with room for at least `capacity` elements.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let arena = Self::new();
+        let chunks = (0..capacity.div_ceil(N))
+            .map(|_| Self::new_chunk())
+            .collect();
+        *arena.chunks.borrow_mut() = chunks;
+        arena
+    }
+
+    /// Allocates a new element in the arena and returns its id.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the arena already has `u32::MAX + 1` chunks. The element is dropped then.
+    pub fn alloc(&self, elem: T) -> ArenaId<T> {
+        let len = self.len.get();
+        let (chunk, slot) = (len / N, len % N);
+        // Check this before the element is written, so that it isn't counted in the length.
+        let chunk_id = u32::try_from(chunk).expect("too many chunks");
+        let mut chunks = self.chunks.borrow_mut();
+        // Chunks that were allocated in advance are already in the table.
+        if chunk == chunks.len() {
+            chunks.push(Self::new_chunk());
+        }
+        // The slot is not initialized yet, so nobody else is looking at it.
+        unsafe { chunks[chunk].cast::<T>().add(slot).write(elem) };
+        self.len.set(len + 1);
+        ArenaId {
+            chunk: chunk_id,
+            slot: slot as u32,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a reference to the element with the given id.
+    ///
+    /// Ids don't remember their arena, so an id from another arena gives an unrelated
+    /// element if it is in bounds for this arena.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the id is out of bounds, which means it was returned by another arena.
+    pub fn get(&self, id: ArenaId<T>) -> &T {
+        // The element is initialized, and no mutable reference to it can exist while the
+        // arena is borrowed.
+        unsafe { self.slot(id).as_ref() }
+    }
+
+    /// Returns a mutable reference to the element with the given id.
+    ///
+    /// Ids don't remember their arena, so an id from another arena gives an unrelated
+    /// element if it is in bounds for this arena.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the id is out of bounds, which means it was returned by another arena.
+    pub fn get_mut(&mut self, id: ArenaId<T>) -> &mut T {
+        unsafe { self.slot(id).as_mut() }
+    }
+
+    /// Returns an iterator over the ids and elements of the arena, in allocation order.
+    ///
+    /// Elements allocated while iterating are not visited.
+    pub fn iter(&self) -> Iter<'_, N, T> {
+        Iter {
+            arena: self,
+            index: 0,
+            len: self.len(),
+        }
+    }
+
+    /// Returns an iterator over the ids and mutable references to the elements of the
+    /// arena, in allocation order.
+    pub fn iter_mut(&mut self) -> IterMut<'_, N, T> {
+        IterMut {
+            len: self.len(),
+            chunks: self.chunks.get_mut(),
+            index: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the number of elements in the arena.
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the id of the element at the given position in allocation order.
+    pub(crate) fn id_at(&self, index: usize) -> ArenaId<T> {
+        debug_assert!(index < self.len());
+        // Allocated elements always have ids that fit.
+        ArenaId {
+            chunk: (index / N) as u32,
+            slot: (index % N) as u32,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a pointer to the initialized slot with the given id.
+    fn slot(&self, id: ArenaId<T>) -> NonNull<T> {
+        let (chunk, slot) = (id.chunk as usize, id.slot as usize);
+        assert!(
+            slot < N && chunk * N + slot < self.len(),
+            "the id belongs to another arena"
+        );
+        unsafe { self.chunks.borrow()[chunk].cast::<T>().add(slot) }
+    }
+
+    /// Allocates an uninitialized chunk. Zero-sized elements don't need any memory.
+    fn new_chunk() -> NonNull<[MaybeUninit<T>; N]> {
+        if Self::IS_ZST {
+            return NonNull::dangling();
+        }
+        let layout = Layout::new::<[MaybeUninit<T>; N]>();
+        match NonNull::new(unsafe { alloc(layout) }) {
+            Some(ptr) => ptr.cast(),
+            None => handle_alloc_error(layout),
+        }
+    }
+}
+
+impl<const N: usize, T> Default for IndexArena<N, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, T> Index<ArenaId<T>> for IndexArena<N, T> {
+    type Output = T;
+
+    fn index(&self, id: ArenaId<T>) -> &T {
+        self.get(id)
+    }
+}
+
+impl<const N: usize, T> IndexMut<ArenaId<T>> for IndexArena<N, T> {
+    fn index_mut(&mut self, id: ArenaId<T>) -> &mut T {
+        self.get_mut(id)
+    }
+}
+
+impl<'a, const N: usize, T> IntoIterator for &'a IndexArena<N, T> {
+    type Item = (ArenaId<T>, &'a T);
+    type IntoIter = Iter<'a, N, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, const N: usize, T> IntoIterator for &'a mut IndexArena<N, T> {
+    type Item = (ArenaId<T>, &'a mut T);
+    type IntoIter = IterMut<'a, N, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<const N: usize, T> IntoIterator for IndexArena<N, T> {
+    type Item = T;
+    type IntoIter = IntoIter<N, T>;
+
+    /// Returns an iterator that moves the elements out of the arena, in allocation order.
+    fn into_iter(self) -> Self::IntoIter {
+        // The iterator takes over the elements, so the arena only frees the chunks.
+        let len = self.len.replace(0);
+        IntoIter {
+            arena: self,
+            index: 0,
+            len,
+        }
+    }
+}
+
+/// An iterator over the ids and elements of an [`IndexArena`], created by
+/// [`IndexArena::iter`].
+pub struct Iter<'a, const N: usize, T> {
+    arena: &'a IndexArena<N, T>,
+    index: usize,
+    /// Elements allocated after the iterator was created are not visited.
+    len: usize,
+}
+
+impl<'a, const N: usize, T> Iterator for Iter<'a, N, T> {
+    type Item = (ArenaId<T>, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index == self.len {
+            return None;
+        }
+        let id = self.arena.id_at(self.index);
+        self.index += 1;
+        Some((id, self.arena.get(id)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+/// An iterator over the ids and mutable references to the elements of an [`IndexArena`],
+/// created by [`IndexArena::iter_mut`].
+pub struct IterMut<'a, const N: usize, T> {
+    chunks: &'a [NonNull<[MaybeUninit<T>; N]>],
+    index: usize,
+    len: usize,
+    /// The iterator hands out mutable references, so it must be invariant in `T`.
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, const N: usize, T> Iterator for IterMut<'a, N, T> {
+    type Item = (ArenaId<T>, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index == self.len {
+            return None;
+        }
+        let (chunk, slot) = (self.index / N, self.index % N);
+        self.index += 1;
+        let id = ArenaId {
+            chunk: chunk as u32,
+            slot: slot as u32,
+            _marker: PhantomData,
+        };
+        // The arena is borrowed mutably, and every element is only visited once.
+        let elem = unsafe { &mut *self.chunks[chunk].cast::<T>().add(slot).as_ptr() };
+        Some((id, elem))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+/// An iterator that moves the elements out of an [`IndexArena`], created by its
+/// `IntoIterator` implementation.
+///
+/// Elements that are not consumed are dropped together with the iterator.
+pub struct IntoIter<const N: usize, T> {
+    /// The arena has a length of zero, so it only frees the chunks when it is dropped.
+    arena: IndexArena<N, T>,
+    index: usize,
+    len: usize,
+}
+
+impl<const N: usize, T> Iterator for IntoIter<N, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.index == self.len {
+            return None;
+        }
+        let (chunk, slot) = (self.index / N, self.index % N);
+        self.index += 1;
+        Some(unsafe {
+            self.arena.chunks.get_mut()[chunk]
+                .cast::<T>()
+                .add(slot)
+                .read()
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<const N: usize, T> Drop for IntoIter<N, T> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+impl<const N: usize, T> Drop for IndexArena<N, T> {
+    fn drop(&mut self) {
+        let mut remaining = self.len.get();
+        for chunk in self.chunks.get_mut().drain(..) {
+            let len = remaining.min(N);
+            remaining -= len;
+            unsafe {
+                let elems = core::ptr::slice_from_raw_parts_mut(chunk.cast::<T>().as_ptr(), len);
+                core::ptr::drop_in_place(elems);
+                if !Self::IS_ZST {
+                    dealloc(chunk.cast().as_ptr(), Layout::new::<[MaybeUninit<T>; N]>());
+                }
+            }
+        }
+    }
+}
+
+// The trait implementations of `ArenaId` don't need any bounds on `T`, so they aren't
+// derived.
+
+impl<T> Clone for ArenaId<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ArenaId<T> {}
+
+impl<T> PartialEq for ArenaId<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.chunk, self.slot) == (other.chunk, other.slot)
+    }
+}
+
+impl<T> Eq for ArenaId<T> {}
+
+impl<T> Hash for ArenaId<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.chunk, self.slot).hash(state);
+    }
+}
+
+impl<T> fmt::Debug for ArenaId<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArenaId")
+            .field("chunk", &self.chunk)
+            .field("slot", &self.slot)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::{ArenaId, IndexArena};
+
+    #[test]
+    fn alloc_and_get() {
+        let arena = IndexArena::<3, String>::new();
+        assert!(arena.is_empty());
+        let ids: Vec<ArenaId<String>> = (0..7).map(|i| arena.alloc(i.to_string())).collect();
+        // Elements can be read while allocating.
+        let first = arena.get(ids[0]);
+        arena.alloc("7".to_string());
+        assert_eq!(first, "0");
+        assert_eq!(arena.len(), 8);
+        assert!(ids
+            .iter()
+            .map(|&id| &arena[id])
+            .eq(["0", "1", "2", "3", "4", "5", "6"]));
+        assert_eq!(format!("{:?}", ids[4]), "ArenaId { chunk: 1, slot: 1 }");
+    }
+
+    #[test]
+    fn iter() {
+        let arena = IndexArena::<2, i32>::new();
+        let ids: Vec<_> = (0..5).map(|i| arena.alloc(i * 10)).collect();
+        let mut iter = arena.iter();
+        assert_eq!(iter.next(), Some((ids[0], &0)));
+        arena.alloc(50);
+        assert!(iter
+            .map(|(id, &elem)| (id, elem))
+            .eq(ids[1..].iter().copied().zip([10, 20, 30, 40])));
+    }
+
+    #[test]
+    fn iter_mut_and_into_iter() {
+        let counter = Rc::new(());
+        let mut arena = IndexArena::<2, (i32, Rc<()>)>::with_capacity(3);
+        let ids: Vec<_> = (0..5)
+            .map(|i| arena.alloc((i, Rc::clone(&counter))))
+            .collect();
+        for (id, elem) in &mut arena {
+            elem.0 += 10;
+            assert_eq!(id, ids[elem.0 as usize - 10]);
+        }
+        assert!((&arena).into_iter().map(|(_, elem)| elem.0).eq(10..15));
+        let mut iter = arena.into_iter();
+        assert_eq!(iter.size_hint(), (5, Some(5)));
+        assert_eq!(iter.next().map(|elem| elem.0), Some(10));
+        assert_eq!(Rc::strong_count(&counter), 5);
+        drop(iter);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn with_capacity() {
+        let arena = IndexArena::<4, String>::with_capacity(5);
+        let first = arena.alloc(String::from("a"));
+        for i in 0..3 {
+            arena.alloc(i.to_string());
+        }
+        assert_eq!(arena.chunks.borrow().len(), 2);
+        assert_eq!(arena[first], "a");
+        // The chunk that was allocated in advance is freed without dropping its slots.
+        drop(arena);
+        let empty = IndexArena::<4, String>::with_capacity(1);
+        assert!(empty.into_iter().next().is_none());
+    }
+
+    #[test]
+    fn graph() {
+        struct Node {
+            value: i32,
+            edges: Vec<ArenaId<Node>>,
+        }
+
+        let mut arena = IndexArena::<2, Node>::new();
+        let ids: Vec<_> = (0..5)
+            .map(|value| {
+                arena.alloc(Node {
+                    value,
+                    edges: Vec::new(),
+                })
+            })
+            .collect();
+        for (&from, &to) in ids.iter().zip(ids.iter().cycle().skip(1)) {
+            arena[from].edges.push(to);
+        }
+        let mut id = ids[0];
+        for _ in 0..7 {
+            id = arena[id].edges[0];
+        }
+        assert_eq!(arena.get(id).value, 2);
+        arena.get_mut(id).value = 20;
+        assert_eq!(arena[ids[2]].value, 20);
+    }
+
+    #[test]
+    #[should_panic = "another arena"]
+    fn id_from_another_arena() {
+        let arena = IndexArena::<3, i32>::new();
+        let other = IndexArena::<3, i32>::new();
+        for i in 0..3 {
+            other.alloc(i);
+        }
+        let id = other.alloc(3);
+        arena.alloc(0);
+        arena.get(id);
+    }
+
+    #[test]
+    fn id_from_another_arena_in_bounds() {
+        let arena = IndexArena::<3, i32>::new();
+        let other = IndexArena::<3, i32>::new();
+        let id = other.alloc(10);
+        arena.alloc(0);
+        assert_eq!(arena[id], 0);
+    }
+
+    #[test]
+    fn drop_elements() {
+        let counter = Rc::new(());
+        let arena = IndexArena::<3, _>::new();
+        for _ in 0..7 {
+            arena.alloc(Rc::clone(&counter));
+        }
+        assert_eq!(Rc::strong_count(&counter), 8);
+        drop(arena);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn zero_sized_types() {
+        let arena = IndexArena::<3, ()>::new();
+        let ids: Vec<_> = (0..5).map(|_| arena.alloc(())).collect();
+        assert_eq!(arena.len(), 5);
+        assert_eq!(arena[ids[4]], ());
+    }
+}
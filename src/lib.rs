@@ -1,17 +1,118 @@
-use std::cell::RefCell;
-use std::marker::PhantomPinned;
-use std::mem::MaybeUninit;
-use std::pin::Pin;
-use std::ptr::NonNull;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::cell::{Cell, Ref, RefCell, RefMut};
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{ManuallyDrop, MaybeUninit};
+#[cfg(feature = "debug-backtrace")]
+use core::panic::Location;
+use core::pin::Pin;
+use core::ptr::NonNull;
+use core::slice;
+#[cfg(feature = "debug-backtrace")]
+use std::collections::HashMap;
+
+pub mod any;
+pub mod append;
+pub mod array;
+#[cfg(feature = "ast")]
+pub mod ast;
+pub mod bytes;
+pub mod collections;
+pub mod compat;
 pub mod double;
+pub mod dynamic;
+pub mod fixed;
+pub mod frame;
+pub mod frozen;
+pub mod id_arena;
+pub mod index;
+pub mod pool;
+#[cfg(feature = "rayon")]
+mod rayon_impls;
+pub mod rolling;
+#[cfg(feature = "serde")]
+mod serde_impls;
+#[cfg(feature = "std")]
+pub mod sync;
+pub mod tagged;
 
-pub struct Arena<const N: usize, T> {
-    inner: RefCell<Option<InnerArena<N, T>>>,
+pub use allocator_api2::alloc::{Allocator, Global};
+use frozen::FrozenArena;
+
+/// An arena allocator made up of a linked list of chunks with `N` slots each.
+///
+/// `Arena` is invariant in `T`. Elements can be allocated through a shared reference, so
+/// a covariant arena would allow storing short-lived values in an arena whose type
+/// promises a longer lifetime:
+///
+/// ```compile_fail
+/// use linked_list_arena::Arena;
+///
+/// fn shorten<'a>(arena: &'a Arena<4, &'static str>) -> &'a Arena<4, &'a str> {
+///     arena
+/// }
+/// ```
+///
+/// Dropping the arena drops all elements, in reverse allocation order like local variables,
+/// see [`Arena::destroy`]. Like with `Vec<T>`, elements may hold references
+/// to each other, as long as their destructors don't use them:
+///
+/// ```compile_fail
+/// use std::cell::Cell;
+/// use linked_list_arena::Arena;
+///
+/// struct Node<'a>(Cell<Option<&'a Node<'a>>>);
+///
+/// impl Drop for Node<'_> {
+///     fn drop(&mut self) {
+///         // This could read an element that has already been dropped.
+///         let _ = self.0.get().map(|other| other.0.get());
+///     }
+/// }
+///
+/// let arena = Arena::<4, Node>::new();
+/// let a = arena.alloc(Node(Cell::new(None)));
+/// let b = arena.alloc(Node(Cell::new(Some(a))));
+/// a.0.set(Some(b));
+/// ```
+///
+/// `Arena` doesn't implement `Clone`, because cloning through a shared reference would
+/// read elements while mutable references to them may be alive. Use [`Arena::snapshot`]
+/// instead:
+///
+/// ```compile_fail
+/// let arena = linked_list_arena::Arena::<4, i32>::new();
+/// let elem = arena.alloc(1);
+/// let copy = arena.clone();
+/// *elem = 2;
+/// ```
+///
+/// For the same reason, the `serde` feature only implements `Deserialize` for `Arena`, not
+/// `Serialize`. Serialize a [`FrozenArena`] instead, or the elements
+/// with `serializer.collect_seq(arena.iter())`.
+///
+/// Chunks are allocated from the allocator `A`, see [`Arena::new_in`].
+pub struct Arena<const N: usize, T, A: Allocator = Global> {
+    /// An `Option<InnerArena<N, T>>`, see [`ErasedInner`].
+    inner: RefCell<ErasedInner<N, A>>,
+    /// The arena owns its elements, and `Cell` makes it invariant in `T`.
+    _marker: PhantomData<Cell<T>>,
+    /// The number of allocations made from each source location.
+    #[cfg(feature = "debug-backtrace")]
+    sites: RefCell<HashMap<&'static Location<'static>, usize>>,
 }
 
 struct InnerArena<const N: usize, T> {
     /// A link to the first element of a linked list of arena chunks.
+    ///
+    /// Reading the link doesn't create a reference to the chunk, which would invalidate
+    /// the references to its elements.
     head_chunk: Link<N, T>,
     /// A pointer to the next object to be allocated.
     ptr: NonNull<MaybeUninit<T>>,
@@ -21,234 +122,3457 @@ struct InnerArena<const N: usize, T> {
     /// the end of the chunk. The reason is that MIRI then complains because of something
     /// to do with tagged pointers.
     end: NonNull<MaybeUninit<T>>,
+    /// Chunks that are currently unused, linked through their `next` field.
+    ///
+    /// They are used before new chunks are allocated.
+    spare: Option<Link<N, T>>,
+    /// The number of initialized elements in all chunks except the head chunk.
+    len_before_head: usize,
+    /// The number of chunks in the linked list and in `spare` together.
+    chunk_count: usize,
+    /// The number of slots in all chunks of the linked list and in `spare` together.
+    capacity: usize,
+}
+
+/// Storage for an `Option<InnerArena<N, T>>` whose type doesn't mention `T`.
+///
+/// If `Arena` implemented `Drop` itself, the drop checker would require all lifetimes in
+/// `T` to strictly outlive the arena, so elements couldn't reference each other. Instead,
+/// the elements are dropped through a function pointer by this type, which has no `T` to
+/// check. This is what `#[may_dangle]` achieves for `Vec<T>`; the `PhantomData` in `Arena`
+/// still makes the drop checker look at the destructor of `T`.
+///
+/// The allocator is stored here as well, because the chunks are freed with it.
+struct ErasedInner<const N: usize, A: Allocator> {
+    /// Has the size and alignment of `Option<InnerArena<N, T>>`, because `InnerArena`
+    /// only holds pointers.
+    inner: MaybeUninit<Option<InnerArena<N, ()>>>,
+    /// The number of elements if `T` is zero-sized. Zero-sized elements don't need any
+    /// memory, so no chunks are allocated for them.
+    zst_len: usize,
+    /// Whether elements have been allocated with [`Arena::alloc_pinned`]. These must not be
+    /// moved or handed out as `&mut T` until they are dropped.
+    pinned: bool,
+    /// The sequence number of the chunk that last became the head chunk. It is kept when
+    /// the chunks are freed, so that sequence numbers are never reused.
+    chunk_seq: usize,
+    alloc: A,
+    drop_inner: unsafe fn(&mut ErasedInner<N, A>),
 }
 
-type Link<const N: usize, T> = Pin<Box<Chunk<N, T>>>;
+/// An owning pointer to a chunk, which has to be freed with [`Chunk::free`].
+///
+/// Chunks are never moved, so pointers to their slots stay valid.
+type Link<const N: usize, T> = NonNull<Chunk<N, T>>;
 
+/// A chunk with `N` slots.
+///
+/// [`Arena::alloc_extend`] allocates chunks with more than `N` slots for elements that
+/// don't fit into a regular chunk. Such a chunk is a larger allocation whose slots continue
+/// past the end of the `slots` array, so the slots are the last field and the layout is
+/// fixed with `repr(C)`. The slots are only accessed through [`Chunk::slots_ptr`], whose
+/// pointer may be used for all of them.
+#[repr(C)]
 struct Chunk<const N: usize, T> {
-    slots: [MaybeUninit<T>; N],
     next: Option<Link<N, T>>,
-    _pin: PhantomPinned,
+    /// The number of initialized elements in the `next` chunk.
+    ///
+    /// This is usually the capacity of that chunk, but [`Arena::alloc_extend`] can leave
+    /// free slots behind. It is stored here rather than in the `next` chunk itself, because
+    /// that chunk may no longer be touched once references to its elements have been
+    /// handed out.
+    next_len: usize,
+    /// The number of slots, which is at least `N`.
+    capacity: usize,
+    /// Identifies the chunk for [`Checkpoint`]s. It is taken from [`ErasedInner::chunk_seq`]
+    /// whenever the chunk becomes the head chunk, so unlike the address of the chunk, it
+    /// isn't shared with a chunk that was freed or reused since.
+    seq: usize,
+    slots: [MaybeUninit<T>; N],
+}
+
+impl<const N: usize, T> Chunk<N, T> {
+    /// Allocates a new chunk with `N` slots that isn't linked to any other chunk, or returns
+    /// `None` if the allocation fails.
+    fn try_new_in(alloc: &impl Allocator) -> Option<Link<N, T>> {
+        Self::try_with_capacity_in(N, alloc)
+    }
+
+    /// Allocates a new chunk with `capacity` slots, where `capacity` is at least `N`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the chunk would be larger than `isize::MAX` bytes.
+    fn try_with_capacity_in(capacity: usize, alloc: &impl Allocator) -> Option<Link<N, T>> {
+        let ptr = alloc.allocate(Self::layout(capacity)).ok()?.cast::<Self>();
+        unsafe {
+            // The slots may stay uninitialized. Writing the other fields one by one avoids
+            // building the whole chunk on the stack first.
+            let chunk = ptr.as_ptr();
+            core::ptr::addr_of_mut!((*chunk).next).write(None);
+            core::ptr::addr_of_mut!((*chunk).next_len).write(0);
+            core::ptr::addr_of_mut!((*chunk).capacity).write(capacity);
+            core::ptr::addr_of_mut!((*chunk).seq).write(0);
+        }
+        Some(ptr)
+    }
+
+    /// Returns the layout of a chunk with `capacity` slots, where `capacity` is at least
+    /// `N`. For `N` slots, this is the layout of `Chunk<N, T>`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the chunk would be larger than `isize::MAX` bytes.
+    fn layout(capacity: usize) -> Layout {
+        (capacity - N)
+            .checked_mul(core::mem::size_of::<T>())
+            .and_then(|extra| extra.checked_add(core::mem::size_of::<Self>()))
+            .and_then(|size| Layout::from_size_align(size, core::mem::align_of::<Self>()).ok())
+            .expect("capacity overflow")
+    }
+
+    /// Returns a pointer past the last slot of the chunk.
+    ///
+    /// # Safety
+    ///
+    /// The chunk must not have been freed.
+    unsafe fn end_ptr(chunk: Link<N, T>) -> NonNull<MaybeUninit<T>> {
+        Self::slots_ptr(chunk).add((*chunk.as_ptr()).capacity)
+    }
+
+    /// Returns a pointer to the first slot of the chunk.
+    ///
+    /// No reference to the chunk is created, so pointers into its slots that were derived
+    /// the same way stay valid. Every pointer that the arena keeps or hands out is derived
+    /// from this one.
+    ///
+    /// # Safety
+    ///
+    /// The chunk must not have been freed.
+    unsafe fn slots_ptr(chunk: Link<N, T>) -> NonNull<MaybeUninit<T>> {
+        NonNull::new_unchecked(core::ptr::addr_of_mut!((*chunk.as_ptr()).slots)).cast()
+    }
+
+    /// Frees a chunk without touching its slots.
+    ///
+    /// # Safety
+    ///
+    /// The chunk must have been allocated by `alloc`, and it must not be used afterwards.
+    unsafe fn free(chunk: Link<N, T>, alloc: &impl Allocator) {
+        let capacity = (*chunk.as_ptr()).capacity;
+        alloc.deallocate(chunk.cast(), Self::layout(capacity));
+    }
+}
+
+/// Checks that a chunk with `N` slots of type `T` is non-empty and that its size in bytes
+/// fits in an `isize`, so that pointer offsets within a chunk cannot overflow.
+///
+/// This is meant to be evaluated in a `const` block, which turns a failure into a
+/// compile-time error.
+const fn check_chunk_size<const N: usize, T>() {
+    assert!(N != 0, "chunks must have room for at least one element");
+    let fits = match N.checked_mul(core::mem::size_of::<T>()) {
+        Some(bytes) => bytes <= isize::MAX as usize,
+        None => false,
+    };
+    assert!(fits, "the size of a chunk in bytes must fit in an `isize`");
+}
+
+/// Allocates an uninitialized array of `N` slots directly on the heap.
+///
+/// `Box::new([const { MaybeUninit::uninit() }; N])` may build the array on the stack first,
+/// which overflows the stack for large chunks in debug builds.
+fn new_uninit_slots<const N: usize, T>() -> alloc::boxed::Box<[MaybeUninit<T>; N]> {
+    // An array of `MaybeUninit`s doesn't need to be initialized.
+    unsafe { alloc::boxed::Box::new_uninit().assume_init() }
 }
 
 impl<const N: usize, T> Arena<N, T> {
     /// Creates a new arena.
     /// This function does not allocate any memory.
+    ///
+    /// It is a compile-time error if `N` is zero or if a chunk would be larger than
+    /// `isize::MAX` bytes:
+    ///
+    /// ```compile_fail
+    /// let arena = linked_list_arena::Arena::<0, i32>::new();
+    /// ```
     pub fn new() -> Self {
-        assert!(std::mem::size_of::<T>() != 0);
+        Self::new_in(Global)
+    }
+
+    /// Creates a new arena with enough chunks for `capacity` elements, see
+    /// [`Arena::reserve`].
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_in(capacity, Global)
+    }
+
+    /// Moves all elements of `other` into this arena, without moving them in memory.
+    ///
+    /// The chunks of `other` are linked into this arena, so pointers to its elements stay
+    /// valid. Its elements count as allocated after the elements of this arena, and the
+    /// free slots of the current chunk of this arena are skipped.
+    ///
+    /// This is only available for the global allocator, because the chunks of both arenas
+    /// are freed by this arena. Checkpoints taken before can't be used with
+    /// [`Arena::rollback_to`] afterwards.
+    pub fn append(&mut self, mut other: Self) {
+        #[cfg(feature = "debug-backtrace")]
+        for (site, count) in other.sites.get_mut().drain() {
+            *self.sites.get_mut().entry(site).or_default() += count;
+        }
+        let pinned = other.inner.get_mut().pinned;
+        let (theirs, zst_len, _) = other.into_parts();
+        let inner = self.inner.get_mut();
+        inner.pinned |= pinned;
+        inner.zst_len = inner
+            .zst_len
+            .checked_add(zst_len)
+            .expect("too many elements");
+        let Some(theirs) = theirs else {
+            return;
+        };
+        // The chunks of `other` are numbered again, so that they don't share sequence
+        // numbers with the chunks of this arena.
+        let mut cur_link = Some(theirs.head_chunk);
+        while let Some(link) = cur_link {
+            unsafe {
+                (*link.as_ptr()).seq = inner.next_chunk_seq();
+                cur_link = (*link.as_ptr()).next;
+            }
+        }
+        let appended = match self.inner_mut().take() {
+            Some(mine) => mine.append(theirs),
+            None => theirs,
+        };
+        *self.inner_mut() = Some(appended);
+    }
+}
+
+impl<const N: usize, T, A: Allocator> Arena<N, T, A> {
+    const IS_ZST: bool = core::mem::size_of::<T>() == 0;
+
+    /// Creates a new arena that allocates its chunks from the given allocator.
+    /// This function does not allocate any memory.
+    pub fn new_in(alloc: A) -> Self {
+        const { check_chunk_size::<N, T>() };
         Arena {
-            inner: RefCell::new(None),
+            inner: RefCell::new(ErasedInner::new_in::<T>(alloc)),
+            _marker: PhantomData,
+            #[cfg(feature = "debug-backtrace")]
+            sites: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Creates a new arena that allocates its chunks from the given allocator, with enough
+    /// chunks for `capacity` elements.
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        let arena = Self::new_in(alloc);
+        arena.reserve(capacity);
+        arena
+    }
+
+    /// Allocates enough chunks up front that the next `additional` calls to
+    /// [`Arena::alloc`] don't have to allocate any memory.
+    ///
+    /// The new chunks are kept for reuse like the ones of [`Arena::clear`]. Allocating
+    /// several elements next to each other with [`Arena::alloc_extend`] may leave slots
+    /// unused, so it can need more chunks.
+    pub fn reserve(&self, additional: usize) {
+        if Self::IS_ZST || additional == 0 {
+            return;
+        }
+        if self.inner_ref().is_none() {
+            self.push_chunk(1)
+                .unwrap_or_else(|| alloc::alloc::handle_alloc_error(Layout::new::<Chunk<N, T>>()));
+        }
+        let available = self.inner_ref().as_ref().map_or(0, |arena| {
+            let free_slots = unsafe { arena.end.offset_from(arena.ptr) } as usize;
+            free_slots + count_slots(arena.spare)
+        });
+        for _ in 0..additional.saturating_sub(available).div_ceil(N) {
+            let mut chunk = Chunk::try_new_in(&self.inner.borrow().alloc)
+                .unwrap_or_else(|| alloc::alloc::handle_alloc_error(Layout::new::<Chunk<N, T>>()));
+            let mut inner = self.inner();
+            let arena = inner.as_mut().unwrap();
+            unsafe { chunk.as_mut() }.next = arena.spare.take();
+            arena.spare = Some(chunk);
+            arena.chunk_count += 1;
+            arena.capacity += N;
         }
     }
 
     /// Allocates a new element in the arena and returns a mutable reference to it.
     #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
     pub fn alloc(&self, elem: T) -> &mut T {
-        // Check whether anything has been allocated yet.
-        if let Some(arena) = self.inner.borrow_mut().as_mut() {
-            let mut ptr = arena.ptr;
-            // Check whether there is still space in the current chunk.
-            if ptr < arena.end {
-                let slot = unsafe {
-                    // Advance the pointer and turn the pointer into a mutable reference.
-                    arena.ptr = ptr.add(1);
-                    ptr.as_mut()
-                };
-                return slot.write(elem);
-            }
-        }
-
-        // We either haven't allocated anything yet or the current chunk is full.
-        // Both mean we have to allocate a new chunk.
-        let old_head = self.inner.take().map(|a| a.head_chunk);
-        let mut new_chunk = Box::into_pin(Box::new(Chunk {
-            slots: [const { MaybeUninit::uninit() }; N],
-            // The link to the previous head is stored in the new chunk.
-            next: old_head,
-            _pin: PhantomPinned,
-        }));
-
+        #[cfg(feature = "debug-backtrace")]
+        self.record_site();
+        if Self::IS_ZST {
+            let mut ptr = self.alloc_zsts(1);
+            return unsafe {
+                ptr.write(elem);
+                ptr.as_mut()
+            };
+        }
+        let mut arena = self.inner_with_free_slots(1);
+        let mut ptr = arena.ptr;
         let slot = unsafe {
-            // Get a mutable reference to the new chunk.
-            // We have to be careful here, because the chunks are pinned, so we may
-            // not use the mutable reference to move the chunk in memory.
-            let new_chunk_mut = new_chunk.as_mut().get_unchecked_mut();
-            // Get a pointer to the first slot in the new chunk.
-            let mut ptr = NonNull::new_unchecked(new_chunk_mut.slots.as_mut_ptr());
-            // We store the link to the new chunk in the arena.
-            self.inner.replace(Some(InnerArena {
-                head_chunk: new_chunk,
-                ptr: ptr.add(1),
-                end: ptr.add(N),
-            }));
+            // Advance the pointer and turn the pointer into a mutable reference.
+            arena.ptr = ptr.add(1);
             ptr.as_mut()
         };
         slot.write(elem)
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.inner.borrow().is_none()
+    /// Allocates a new element in the arena and returns a raw pointer to it.
+    ///
+    /// The pointer isn't tied to a borrow of the arena, so it can be stored in intrusive
+    /// data structures or handed to C code. Elements never move, so the pointer stays valid
+    /// until the arena is dropped, cleared, rolled back past the element or destroyed; the
+    /// element is still dropped by the arena. Like any raw pointer, it must not be used
+    /// while a reference to the element obtained in another way (e.g. from
+    /// [`Arena::iter_mut`]) is alive.
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn alloc_raw(&self, elem: T) -> NonNull<T> {
+        #[cfg(feature = "debug-backtrace")]
+        self.record_site();
+        let ptr = if Self::IS_ZST {
+            self.alloc_zsts(1)
+        } else {
+            let mut arena = self.inner_with_free_slots(1);
+            let ptr = arena.ptr;
+            arena.ptr = unsafe { ptr.add(1) };
+            ptr.cast()
+        };
+        // The pointer is derived from the chunk and not from a reference, so later
+        // allocations don't invalidate it.
+        unsafe { ptr.write(elem) };
+        ptr
     }
 
-    /// Returns the number of free slots in the current chunk.
-    /// If no chunk has been allocated yet, `None` is returned.
-    pub fn free_slots_in_current_chunk(&self) -> Option<usize> {
-        self.inner
-            .borrow()
-            .as_ref()
-            .map(|arena| unsafe { arena.end.offset_from(arena.ptr) as usize })
+    /// Allocates a new element in the arena and returns a mutable reference to it.
+    ///
+    /// Unlike [`Arena::alloc`], this doesn't abort if a new chunk can't be allocated, but
+    /// returns an error that holds the element.
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn try_alloc(&self, elem: T) -> Result<&mut T, AllocError<T>> {
+        self.try_alloc_with(|| elem)
+            .map_err(|err| AllocError(err.into_inner()()))
     }
 
-    /// Consumes the arena and destroys it.
+    /// Allocates a new element that is constructed by the closure and returns a mutable
+    /// reference to it.
     ///
-    /// This is potentially more efficient than relying on the default Drop implementation,
-    /// but it has the disadvantage that it cannot be used if there are internal references
-    /// between the elements in the arena.
+    /// This is the fallible version of [`Arena::alloc_with`]. If a new chunk can't be
+    /// allocated, the closure is not called and is returned in the error instead.
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn try_alloc_with<F: FnOnce() -> T>(&self, f: F) -> Result<&mut T, AllocError<F>> {
+        if Self::IS_ZST {
+            return Ok(self.alloc_with(f));
+        }
+        let Some(mut arena) = self.try_inner_with_free_slots(1) else {
+            return Err(AllocError(f));
+        };
+        #[cfg(feature = "debug-backtrace")]
+        self.record_site();
+        let mut ptr = arena.ptr;
+        // The pointer is only advanced once we have the value, see `alloc_with_addr`.
+        let elem = f();
+        let slot = unsafe {
+            arena.ptr = ptr.add(1);
+            ptr.as_mut()
+        };
+        Ok(slot.write(elem))
+    }
+
+    /// Allocates a new element whose value depends on its own address.
     ///
-    /// This also calls the destructor of all elements in the arena.
-    pub fn destroy(self) {
-        if let Some(arena) = self.inner.into_inner() {
-            unsafe {
-                let mut head_chunk = Pin::into_inner_unchecked(arena.head_chunk);
-                // Iterate over the elements in `head_chunk.slots` until `arena.ptr`
-                // and call `assume_init_drop()` on each of them, because we know that they
-                // have been initialized.
-                let mut ptr = NonNull::new_unchecked(head_chunk.slots.as_mut_ptr());
-                while ptr < arena.ptr {
-                    ptr.as_mut().assume_init_drop();
-                    ptr = ptr.add(1);
-                }
+    /// The slot for the element is reserved first, and the closure receives a pointer to
+    /// it. The value returned by the closure is then written to that slot. This way, the
+    /// element can store pointers to itself (or hand them out), and they stay valid as long
+    /// as the element is not moved out of the arena. The pointer must not be dereferenced
+    /// inside the closure, because the slot is not initialized yet.
+    ///
+    /// The arena stays borrowed while the closure runs, so the closure must not use the
+    /// arena; doing so panics. If the closure panics, nothing is allocated.
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn alloc_with_addr(&self, f: impl FnOnce(NonNull<T>) -> T) -> &mut T {
+        #[cfg(feature = "debug-backtrace")]
+        self.record_site();
+        if Self::IS_ZST {
+            let elem = f(NonNull::dangling());
+            let mut ptr = self.alloc_zsts(1);
+            return unsafe {
+                ptr.write(elem);
+                ptr.as_mut()
+            };
+        }
+        let mut arena = self.inner_with_free_slots(1);
+        let mut ptr = arena.ptr;
+        // The pointer is only advanced once we have the value, so that a panic in the
+        // closure does not leave an uninitialized slot behind.
+        let elem = f(ptr.cast());
+        let slot = unsafe {
+            arena.ptr = ptr.add(1);
+            ptr.as_mut()
+        };
+        slot.write(elem)
+    }
 
-                // Iterate over the linked list of chunks and drop all elements.
-                let mut cur_link = head_chunk.next.take();
-                while let Some(boxed_node) = cur_link {
-                    let mut chunk = Pin::into_inner_unchecked(boxed_node);
-                    // In the chunks that are not the head chunk, all elements have been initialized.
-                    chunk.slots.iter_mut().for_each(|slot| {
-                        slot.assume_init_drop();
-                    });
-                    cur_link = chunk.next.take();
-                }
-            }
+    /// Allocates a new element that refers to itself, similar to `Rc::new_cyclic`.
+    ///
+    /// The closure receives a [`SelfRef`] to the reserved slot, which can be stored in the
+    /// element (or in other elements) and turned into a `&T` once the element has been
+    /// allocated. Because the element can be reached through these handles, only a shared
+    /// reference to it is returned. Like with [`Arena::alloc_with_addr`], the closure must
+    /// not use the arena, and nothing is allocated if it panics.
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn alloc_cyclic<'a>(&'a self, f: impl FnOnce(SelfRef<'a, T>) -> T) -> &'a T {
+        self.alloc_with_addr(|ptr| {
+            f(SelfRef {
+                ptr,
+                _marker: PhantomData,
+            })
+        })
+    }
+
+    /// Allocates a new element that is constructed by the closure and returns a mutable
+    /// reference to it.
+    ///
+    /// The slot is reserved before the closure runs, so that the compiler can construct the
+    /// value directly in the chunk instead of on the stack. The arena stays borrowed while
+    /// the closure runs, so the closure must not use the arena; doing so panics.
+    #[allow(clippy::mut_from_ref)]
+    #[inline]
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn alloc_with(&self, f: impl FnOnce() -> T) -> &mut T {
+        self.alloc_with_addr(|_| f())
+    }
+
+    /// Allocates a new element in the arena and returns a pinned reference to it.
+    ///
+    /// Elements are never moved by the arena, so they can be pinned in place, e.g. to poll
+    /// futures or to build self-referential values. To uphold the pinning guarantee,
+    /// methods that would hand out `&mut T` to all elements or move them out of the arena
+    /// panic once an element has been pinned, until the arena is cleared.
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn alloc_pinned(&self, elem: T) -> Pin<&mut T> {
+        self.inner.borrow_mut().pinned = true;
+        // The element is only dropped in place, and the flag keeps it from being moved.
+        unsafe { Pin::new_unchecked(self.alloc(elem)) }
+    }
+
+    /// Reserves an uninitialized slot for a new element.
+    ///
+    /// The element can be written field by field through the returned [`UninitSlot`], and
+    /// is only allocated once the slot is finished with [`UninitSlot::write`] or
+    /// [`UninitSlot::assume_init`]. Dropping the slot without finishing it allocates
+    /// nothing. Like with [`Arena::alloc_with_addr`], the arena must not be used while the
+    /// slot is alive; doing so panics.
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn alloc_uninit(&self) -> UninitSlot<'_, N, T, A> {
+        if Self::IS_ZST {
+            return UninitSlot {
+                arena: self,
+                inner: None,
+                slot: NonNull::dangling(),
+                #[cfg(feature = "debug-backtrace")]
+                site: Location::caller(),
+            };
+        }
+        let inner = self.inner_with_free_slots(1);
+        let slot = inner.ptr;
+        UninitSlot {
+            arena: self,
+            inner: Some(inner),
+            slot,
+            #[cfg(feature = "debug-backtrace")]
+            site: Location::caller(),
         }
     }
-}
 
-impl<const N: usize, T> Default for Arena<N, T> {
-    fn default() -> Self {
-        Self::new()
+    fn inner(&self) -> RefMut<'_, Option<InnerArena<N, T>>> {
+        RefMut::map(self.inner.borrow_mut(), |inner| unsafe {
+            inner.typed_mut()
+        })
     }
-}
 
-#[cfg(test)]
-mod test {
-    use std::cell::Cell;
-    use std::sync::atomic::{AtomicUsize, Ordering};
-    use std::sync::Arc;
+    fn inner_ref(&self) -> Ref<'_, Option<InnerArena<N, T>>> {
+        Ref::map(self.inner.borrow(), |inner| unsafe { inner.typed() })
+    }
 
-    use super::*;
+    fn inner_mut(&mut self) -> &mut Option<InnerArena<N, T>> {
+        unsafe { self.inner.get_mut().typed_mut() }
+    }
 
-    #[test]
-    fn empty_arena() {
-        let arena = Arena::<10, i32>::new();
-        assert!(arena.is_empty());
+    /// Takes the chunks, the number of zero-sized elements and the allocator out of the
+    /// arena.
+    fn into_parts(self) -> (Option<InnerArena<N, T>>, usize, A) {
+        // The allocator can't be moved out of `ErasedInner`, which implements `Drop`, so we
+        // take everything out and forget the arena.
+        let mut this = ManuallyDrop::new(self);
+        #[cfg(feature = "debug-backtrace")]
+        drop(unsafe { core::ptr::read(&this.sites) });
+        let zst_len = core::mem::take(&mut this.inner.get_mut().zst_len);
+        let inner = this.inner_mut().take();
+        let alloc = unsafe { core::ptr::read(&this.inner.get_mut().alloc) };
+        (inner, zst_len, alloc)
     }
 
-    #[test]
-    fn free_slots() {
-        let arena = Arena::<10, i32>::new();
-        arena.alloc(1);
-        assert!(!arena.is_empty());
-        assert_eq!(arena.free_slots_in_current_chunk(), Some(9));
+    /// Counts `len` new zero-sized elements and returns a pointer for them.
+    fn alloc_zsts(&self, len: usize) -> NonNull<T> {
+        let zst_len = &mut self.inner.borrow_mut().zst_len;
+        *zst_len = zst_len.checked_add(len).expect("too many elements");
+        NonNull::dangling()
     }
 
-    #[test]
-    fn deref_allocated_elements() {
-        let arena = Arena::<10, i32>::new();
-        let el1 = arena.alloc(2);
-        let el2 = arena.alloc(3);
-        assert_eq!(*el1, 2);
-        assert_eq!(*el2, 3);
+    /// Allocates the elements of the iterator next to each other and returns them as a
+    /// slice.
+    ///
+    /// If the elements don't fit into the current chunk, they are placed in a new chunk,
+    /// and the free slots of the current chunk stay unused. If there are more than `N`
+    /// elements, the new chunk is made large enough to hold all of them.
+    ///
+    /// If the size hint of the iterator gives its exact length, the elements are written
+    /// straight into the arena, and the iterator must not use the arena; doing so panics.
+    /// Other iterators are collected before anything is allocated, so they may use the
+    /// arena.
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn alloc_extend(&self, iter: impl IntoIterator<Item = T>) -> &mut [T] {
+        let mut iter = iter.into_iter();
+        let mut elems: Vec<T> = match iter.size_hint() {
+            (len, Some(max)) if len == max => match self.alloc_exact(len, &mut iter) {
+                Ok(elems) => return elems,
+                // The iterator yielded more elements than its size hint promised.
+                Err(written) => written.into_iter().chain(iter).collect(),
+            },
+            _ => iter.collect(),
+        };
+        let len = elems.len();
+        let slots = self.alloc_contiguous(len);
+        unsafe {
+            // The elements are moved into the arena, so the vector must not drop them.
+            core::ptr::copy_nonoverlapping(elems.as_ptr(), slots.as_ptr(), len);
+            elems.set_len(0);
+            slice::from_raw_parts_mut(slots.as_ptr(), len)
+        }
     }
 
-    #[test]
-    fn fill_chunk() {
-        let arena = Arena::<3, i32>::new();
+    /// Writes the elements of an iterator that claims to yield `len` elements into
+    /// consecutive slots.
+    ///
+    /// If the iterator yields more elements, nothing is allocated, and the elements are
+    /// returned instead.
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    fn alloc_exact(
+        &self,
+        len: usize,
+        iter: &mut impl Iterator<Item = T>,
+    ) -> Result<&mut [T], Vec<T>> {
+        /// Drops the elements that have been written so far if the iterator panics.
+        struct Written<T> {
+            start: NonNull<T>,
+            len: usize,
+        }
 
-        arena.alloc(1);
-        assert_eq!(arena.free_slots_in_current_chunk(), Some(2));
-        let el1 = arena.alloc(2);
-        assert_eq!(arena.free_slots_in_current_chunk(), Some(1));
-        arena.alloc(3);
-        assert_eq!(arena.free_slots_in_current_chunk(), Some(0));
-        let el2 = arena.alloc(4);
-        assert_eq!(arena.free_slots_in_current_chunk(), Some(2));
-        arena.alloc(5);
-        assert_eq!(arena.free_slots_in_current_chunk(), Some(1));
-        assert_eq!(*el1, 2);
-        assert_eq!(*el2, 4);
-        *el2 = 6;
-        assert_eq!(*el1, 2);
-        assert_eq!(*el2, 6);
-        arena.alloc(6);
-        assert_eq!(arena.free_slots_in_current_chunk(), Some(0));
-        let el3 = arena.alloc(7);
-        assert_eq!(arena.free_slots_in_current_chunk(), Some(2));
-        assert_eq!(*el2, 6);
-        assert_eq!(*el3, 7);
-        arena.destroy();
+        impl<T> Drop for Written<T> {
+            fn drop(&mut self) {
+                let elems = NonNull::slice_from_raw_parts(self.start, self.len);
+                unsafe { elems.drop_in_place() };
+            }
+        }
+
+        // The arena stays borrowed while the iterator runs, because the slots are not
+        // allocated yet. Zero-sized elements don't need any slots.
+        let mut arena = (len > 0 && !Self::IS_ZST).then(|| self.inner_with_free_slots(len));
+        let start = arena
+            .as_ref()
+            .map_or(NonNull::dangling(), |arena| arena.ptr.cast());
+        let mut written = Written { start, len: 0 };
+        for elem in iter.by_ref().take(len) {
+            unsafe { start.add(written.len).write(elem) };
+            written.len += 1;
+        }
+        if let Some(extra) = iter.next() {
+            let mut elems = Vec::with_capacity(written.len + 1);
+            for i in 0..written.len {
+                elems.push(unsafe { start.add(i).read() });
+            }
+            core::mem::forget(written);
+            elems.push(extra);
+            return Err(elems);
+        }
+        let len = written.len;
+        core::mem::forget(written);
+        if len > 0 {
+            #[cfg(feature = "debug-backtrace")]
+            self.record_site();
+        }
+        match &mut arena {
+            Some(arena) => arena.ptr = unsafe { arena.ptr.add(len) },
+            // Either the elements are zero-sized, or there are none.
+            None => {
+                self.alloc_zsts(len);
+            }
+        }
+        Ok(unsafe { slice::from_raw_parts_mut(start.as_ptr(), len) })
     }
 
-    #[cfg(target_pointer_width = "64")]
-    #[test]
-    fn data_structure_size() {
-        assert_eq!(std::mem::size_of::<usize>(), 8);
-        assert_eq!(std::mem::size_of::<InnerArena<1, i32>>(), 24);
-        assert_eq!(std::mem::size_of::<Arena<1, i32>>(), 32);
-        assert_eq!(std::mem::size_of::<Chunk<100, i32>>(), 408);
+    /// Copies the slice into the arena and returns the copy.
+    ///
+    /// See [`Arena::alloc_extend`] for how the elements are placed.
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn alloc_slice_copy(&self, src: &[T]) -> &mut [T]
+    where
+        T: Copy,
+    {
+        let slots = self.alloc_contiguous(src.len());
+        unsafe {
+            core::ptr::copy_nonoverlapping(src.as_ptr(), slots.as_ptr(), src.len());
+            slice::from_raw_parts_mut(slots.as_ptr(), src.len())
+        }
     }
 
-    struct CycleParticipant<'a> {
-        other: Cell<Option<&'a CycleParticipant<'a>>>,
+    /// Reserves `len` slots next to each other and returns a pointer to the first one.
+    ///
+    /// The slots count as initialized, so they have to be written before the arena is
+    /// used again.
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    fn alloc_contiguous(&self, len: usize) -> NonNull<T> {
+        if len == 0 {
+            return NonNull::dangling();
+        }
+        #[cfg(feature = "debug-backtrace")]
+        self.record_site();
+        if Self::IS_ZST {
+            return self.alloc_zsts(len);
+        }
+        let mut arena = self.inner_with_free_slots(len);
+        let ptr = arena.ptr;
+        arena.ptr = unsafe { ptr.add(len) };
+        ptr.cast()
     }
 
-    #[test]
-    fn cycle() {
-        let arena: Arena<10, _> = Arena::new();
+    /// Returns the state of the arena after making sure that the head chunk has at least
+    /// `len` free slots.
+    fn inner_with_free_slots(&self, len: usize) -> RefMut<'_, InnerArena<N, T>> {
+        self.try_inner_with_free_slots(len)
+            .unwrap_or_else(|| alloc::alloc::handle_alloc_error(Chunk::<N, T>::layout(len.max(N))))
+    }
 
-        let a = arena.alloc(CycleParticipant {
-            other: Cell::new(None),
-        });
-        let b = arena.alloc(CycleParticipant {
-            other: Cell::new(None),
+    /// Like [`Arena::inner_with_free_slots`], but returns `None` if a new chunk is needed
+    /// and can't be allocated.
+    fn try_inner_with_free_slots(&self, len: usize) -> Option<RefMut<'_, InnerArena<N, T>>> {
+        // Check whether anything has been allocated yet and whether there is still enough
+        // space in the current chunk.
+        let has_free_slots = matches!(
+            &*self.inner_ref(),
+            Some(arena) if unsafe { arena.end.offset_from(arena.ptr) } as usize >= len
+        );
+        if !has_free_slots {
+            // We either haven't allocated anything yet or the current chunk is full.
+            // Both mean we have to allocate a new chunk.
+            self.push_chunk(len)?;
+        }
+        Some(RefMut::map(self.inner(), |inner| inner.as_mut().unwrap()))
+    }
+
+    /// Makes an empty chunk with at least `len` slots the head chunk, reusing a spare chunk
+    /// if there is one that is large enough.
+    ///
+    /// Returns `None` and leaves the arena unchanged if a new chunk can't be allocated.
+    fn push_chunk(&self, len: usize) -> Option<()> {
+        let spare_chunk = self.inner().as_mut().and_then(|arena| {
+            // Only the chunks for more than `N` elements can be too small.
+            let spare = arena.spare?;
+            (unsafe { (*spare.as_ptr()).capacity } >= len).then(|| arena.pop_spare())?
         });
+        let (new_chunk, new_chunks, new_slots) = match spare_chunk {
+            Some(chunk) => (chunk, 0, 0),
+            None => {
+                let capacity = len.max(N);
+                let alloc = &self.inner.borrow().alloc;
+                (Chunk::try_with_capacity_in(capacity, alloc)?, 1, capacity)
+            }
+        };
+        let (old_head, old_head_len, mut spare, len_before_head, chunk_count, capacity) =
+            match self.inner().take() {
+                Some(arena) => {
+                    let head_len = arena.head_len();
+                    (
+                        Some(arena.head_chunk),
+                        head_len,
+                        arena.spare,
+                        arena.len_before_head + head_len,
+                        arena.chunk_count + new_chunks,
+                        arena.capacity + new_slots,
+                    )
+                }
+                None => (None, 0, None, 0, new_chunks, new_slots),
+            };
 
-        a.other.set(Some(b));
-        b.other.set(Some(a));
+        unsafe {
+            match old_head {
+                // Only the head chunk may be empty, so an empty head that is too small becomes
+                // a spare chunk. The new chunk takes over its sequence number, so that
+                // checkpoints at the start of the empty head now refer to the new chunk.
+                Some(head) if old_head_len == 0 => {
+                    let head = head.as_ptr();
+                    (*new_chunk.as_ptr()).next = (*head).next;
+                    (*new_chunk.as_ptr()).next_len = (*head).next_len;
+                    (*new_chunk.as_ptr()).seq = (*head).seq;
+                    (*head).next = spare;
+                    spare = old_head;
+                }
+                _ => {
+                    // The link to the previous head is stored in the new chunk.
+                    (*new_chunk.as_ptr()).next = old_head;
+                    (*new_chunk.as_ptr()).next_len = old_head_len;
+                    (*new_chunk.as_ptr()).seq = self.inner.borrow_mut().next_chunk_seq();
+                }
+            }
+            // Get a pointer to the first slot in the new chunk.
+            let ptr = Chunk::slots_ptr(new_chunk);
+            // We store the link to the new chunk in the arena.
+            *self.inner() = Some(InnerArena {
+                head_chunk: new_chunk,
+                ptr,
+                end: Chunk::end_ptr(new_chunk),
+                spare,
+                len_before_head,
+                chunk_count,
+                capacity,
+            });
+        }
+        Some(())
     }
 
-    struct WithDrop(i32, Arc<AtomicUsize>);
+    #[cfg(feature = "debug-backtrace")]
+    #[track_caller]
+    fn record_site(&self) {
+        self.record_site_at(Location::caller());
+    }
 
-    impl Drop for WithDrop {
-        fn drop(&mut self) {
-            self.1.fetch_add(1, Ordering::SeqCst);
+    #[cfg(feature = "debug-backtrace")]
+    fn record_site_at(&self, site: &'static Location<'static>) {
+        *self.sites.borrow_mut().entry(site).or_insert(0) += 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        if Self::IS_ZST {
+            return self.inner.borrow().zst_len == 0;
+        }
+        match &*self.inner_ref() {
+            None => true,
+            // The head chunk is only empty if nothing has been allocated in it yet, so no
+            // references into it exist and we may look at its link.
+            Some(arena) => {
+                arena.head_len() == 0 && unsafe { arena.head_chunk.as_ref() }.next.is_none()
+            }
         }
     }
 
-    #[test]
-    fn drop_arena() {
-        let drop_counter = Arc::new(AtomicUsize::new(0));
+    /// Returns the number of elements in the arena.
+    pub fn len(&self) -> usize {
+        let inner = self.inner.borrow();
+        if Self::IS_ZST {
+            return inner.zst_len;
+        }
+        unsafe { inner.typed::<T>() }
+            .as_ref()
+            .map_or(0, |arena| arena.len_before_head + arena.head_len())
+    }
 
-        let arena = Arena::<3, WithDrop>::new();
-        let el1 = arena.alloc(WithDrop(1, Arc::clone(&drop_counter)));
-        arena.alloc(WithDrop(2, Arc::clone(&drop_counter)));
-        arena.alloc(WithDrop(3, Arc::clone(&drop_counter)));
-        arena.alloc(WithDrop(4, Arc::clone(&drop_counter)));
-        arena.alloc(WithDrop(5, Arc::clone(&drop_counter)));
-        arena.alloc(WithDrop(6, Arc::clone(&drop_counter)));
-        arena.alloc(WithDrop(7, Arc::clone(&drop_counter)));
-        assert_eq!(el1.0, 1);
-        arena.destroy(); // Should be calling drop on all elements.
+    /// Returns the number of chunks the arena has allocated, including the chunks that
+    /// [`Arena::clear`] keeps for reuse.
+    pub fn chunk_count(&self) -> usize {
+        self.inner_ref()
+            .as_ref()
+            .map_or(0, |arena| arena.chunk_count)
+    }
+
+    /// Returns the total number of slots in all chunks.
+    ///
+    /// This can be more than the number of elements the arena can hold without allocating,
+    /// because [`Arena::alloc_extend`] may leave slots unused. For zero-sized types, this
+    /// is `usize::MAX`.
+    pub fn capacity(&self) -> usize {
+        if Self::IS_ZST {
+            return usize::MAX;
+        }
+        self.inner_ref().as_ref().map_or(0, |arena| arena.capacity)
+    }
+
+    /// Returns the number of free slots in the current chunk.
+    /// If no chunk has been allocated yet, which is always the case for zero-sized types,
+    /// `None` is returned.
+    pub fn free_slots_in_current_chunk(&self) -> Option<usize> {
+        self.inner_ref()
+            .as_ref()
+            .map(|arena| unsafe { arena.end.offset_from(arena.ptr) as usize })
+    }
+
+    /// Returns the number of bytes allocated for chunks, including the spare ones.
+    pub fn allocated_bytes(&self) -> usize {
+        if Self::IS_ZST {
+            return 0;
+        }
+        // Chunks with more than `N` slots are larger by the size of the extra slots.
+        let extra_slots = self.capacity() - self.chunk_count() * N;
+        self.chunk_count() * core::mem::size_of::<Chunk<N, T>>()
+            + extra_slots * core::mem::size_of::<T>()
+    }
+
+    /// Returns the number of bytes occupied by the elements.
+    pub fn used_bytes(&self) -> usize {
+        self.len() * core::mem::size_of::<T>()
+    }
+
+    /// Returns a summary of the memory usage of the arena.
+    ///
+    /// This doesn't walk the chunks, so it is cheap enough to call for every metrics report.
+    pub fn stats(&self) -> ArenaStats {
+        ArenaStats {
+            chunks: self.chunk_count(),
+            len: self.len(),
+            allocated_bytes: self.allocated_bytes(),
+            used_bytes: self.used_bytes(),
+            free_bytes_in_current_chunk: self.free_slots_in_current_chunk().unwrap_or(0)
+                * core::mem::size_of::<T>(),
+        }
+    }
+
+    /// Returns the number of elements in every chunk of the linked list, in allocation
+    /// order.
+    ///
+    /// Chunks other than the last one can have fewer than `N` elements if
+    /// [`Arena::alloc_extend`] skipped their remaining slots, and the chunks it allocated
+    /// for more than `N` elements have more. Spare chunks are not included.
+    pub fn chunk_lens(&self) -> Vec<usize> {
+        let inner = self.inner_ref();
+        let Some(arena) = &*inner else {
+            return Vec::new();
+        };
+        let mut lens = vec![arena.head_len()];
+        let mut cur_link = Some(arena.head_chunk);
+        while let Some(chunk) = cur_link {
+            // Only the link fields are read, because the slots may be borrowed mutably.
+            unsafe {
+                let chunk = chunk.as_ptr();
+                cur_link = (*chunk).next;
+                if cur_link.is_some() {
+                    lens.push((*chunk).next_len);
+                }
+            }
+        }
+        lens.reverse();
+        lens
+    }
+
+    /// Returns the source locations that allocated elements in this arena, together with
+    /// the number of allocations made there, sorted from most to fewest allocations.
+    ///
+    /// The counts start over when the arena is cleared with [`Arena::clear`]. Elements that
+    /// are removed by [`Arena::pop`] or [`Arena::rollback_to`] are still counted.
+    #[cfg(feature = "debug-backtrace")]
+    pub fn allocation_sites(&self) -> Vec<(&'static Location<'static>, usize)> {
+        let mut sites: Vec<_> = self.sites.borrow().iter().map(|(&l, &c)| (l, c)).collect();
+        sites.sort_by_key(|&(_, count)| core::cmp::Reverse(count));
+        sites
+    }
+
+    /// Calls the closure on every element in the arena, in allocation order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if elements have been pinned with [`Arena::alloc_pinned`].
+    pub fn map_in_place(&mut self, mut f: impl FnMut(&mut T)) {
+        self.assert_not_pinned();
+        for mut chunk in self.chunk_slices() {
+            unsafe { chunk.as_mut() }.iter_mut().for_each(&mut f);
+        }
+    }
+
+    /// Calls the fallible closure on every element in the arena, in allocation order.
+    ///
+    /// Stops at the first error and returns it. Elements after the failing one are not
+    /// visited.
+    ///
+    /// # Panics
+    ///
+    /// Panics if elements have been pinned with [`Arena::alloc_pinned`].
+    pub fn try_map_in_place<E>(
+        &mut self,
+        mut f: impl FnMut(&mut T) -> Result<(), E>,
+    ) -> Result<(), E> {
+        self.assert_not_pinned();
+        for mut chunk in self.chunk_slices() {
+            unsafe { chunk.as_mut() }.iter_mut().try_for_each(&mut f)?;
+        }
+        Ok(())
+    }
+
+    /// Returns an iterator over raw pointers to all elements, in allocation order.
+    ///
+    /// No references to the elements are created, neither by this method nor by the
+    /// iterator. The pointers are valid for reads and writes until the arena is next used
+    /// through `&mut self` or `self`; allocating through `&self` does not invalidate them.
+    /// The iterator does not borrow the arena, so the arena can be used while iterating,
+    /// but elements allocated after this call are not yielded.
+    ///
+    /// This takes `&mut self` to guarantee that no reference handed out by [`Arena::alloc`]
+    /// is alive when the pointers are derived.
+    pub fn iter_ptrs(&mut self) -> impl Iterator<Item = NonNull<T>> {
+        self.chunk_slices().into_iter().flat_map(|chunk| {
+            let start = chunk.cast::<T>();
+            (0..chunk.len()).map(move |i| unsafe { start.add(i) })
+        })
+    }
+
+    /// Returns an iterator over all elements, in allocation order.
+    ///
+    /// This takes `&mut self` rather than `&self` to guarantee that no reference handed out
+    /// by [`Arena::alloc`] is alive while iterating. For the same reason, there is no
+    /// `IntoIterator` implementation for `&Arena`. Use [`Arena::freeze`] to get an arena
+    /// that can be iterated through a shared reference.
+    pub fn iter(&mut self) -> impl Iterator<Item = &T> + '_ {
+        self.chunk_slices()
+            .into_iter()
+            .flat_map(|chunk| unsafe { &*chunk.as_ptr() }.iter())
+    }
+
+    /// Returns an iterator over mutable references to all elements, in allocation order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if elements have been pinned with [`Arena::alloc_pinned`].
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        self.assert_not_pinned();
+        IterMut {
+            chunks: self.chunk_slices().into_iter(),
+            current: [].iter_mut(),
+        }
+    }
+
+    /// Returns a reference to the element with the given index, where the index counts the
+    /// elements in allocation order.
+    ///
+    /// This takes `&mut self` for the same reason as [`Arena::iter`]. The chunks are walked
+    /// from the newest to the oldest one, so this takes `O(chunks)` time for old elements.
+    /// [`FrozenArena::get`] only takes `O(log(chunks))` time.
+    pub fn get(&mut self, index: usize) -> Option<&T> {
+        self.element_ptr(index).map(|ptr| unsafe { ptr.as_ref() })
+    }
+
+    /// Returns a mutable reference to the element with the given index, see [`Arena::get`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if elements have been pinned with [`Arena::alloc_pinned`].
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.assert_not_pinned();
+        self.element_ptr(index)
+            .map(|mut ptr| unsafe { ptr.as_mut() })
+    }
+
+    /// Returns a pointer to the element with the given index, in allocation order.
+    fn element_ptr(&mut self, index: usize) -> Option<NonNull<T>> {
+        if Self::IS_ZST {
+            return (index < self.inner.get_mut().zst_len).then(NonNull::dangling);
+        }
+        let arena = self.inner_mut().as_ref()?;
+        // The index of the first element in the current chunk.
+        let mut start = arena.len_before_head;
+        if index >= start + arena.head_len() {
+            return None;
+        }
+        let mut chunk = arena.head_chunk;
+        while index < start {
+            unsafe {
+                start -= (*chunk.as_ptr()).next_len;
+                chunk = (*chunk.as_ptr()).next?;
+            }
+        }
+        Some(unsafe { Chunk::slots_ptr(chunk).cast::<T>().add(index - start) })
+    }
+
+    /// Returns an iterator over pairs of elements from this arena and `other`, walking both
+    /// arenas in allocation order.
+    ///
+    /// This is meant for parallel arenas that store different components of the same
+    /// entities, where the i-th element of each arena belongs to the same entity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the arenas don't hold the same number of elements, or if elements of either
+    /// arena have been pinned with [`Arena::alloc_pinned`].
+    pub fn zip_mut<'a, const M: usize, U, B: Allocator>(
+        &'a mut self,
+        other: &'a mut Arena<M, U, B>,
+    ) -> impl Iterator<Item = (&'a mut T, &'a mut U)> {
+        self.assert_not_pinned();
+        other.assert_not_pinned();
+        let chunks = self.chunk_slices();
+        let other_chunks = other.chunk_slices();
+        let len: usize = chunks.iter().map(|chunk| chunk.len()).sum();
+        let other_len: usize = other_chunks.iter().map(|chunk| chunk.len()).sum();
+        assert_eq!(
+            len, other_len,
+            "arenas must have the same number of elements"
+        );
+        // Both arenas are borrowed mutably for `'a`, so the slices are unique.
+        let elems = chunks
+            .into_iter()
+            .flat_map(|chunk| unsafe { &mut *chunk.as_ptr() }.iter_mut());
+        let other_elems = other_chunks
+            .into_iter()
+            .flat_map(|chunk| unsafe { &mut *chunk.as_ptr() }.iter_mut());
+        elems.zip(other_elems)
+    }
+
+    /// Panics if elements have been pinned, because they must not be handed out as `&mut T`.
+    fn assert_not_pinned(&mut self) {
+        assert!(
+            !self.inner.get_mut().pinned,
+            "elements of the arena have been pinned"
+        );
+    }
+
+    /// Returns pointers to the initialized part of every chunk, in allocation order.
+    fn chunk_slices(&mut self) -> Vec<NonNull<[T]>> {
+        let mut slices = Vec::new();
+        self.chunk_slices_into(&mut slices);
+        slices
+    }
+
+    /// Replaces the contents of `slices` with pointers to the initialized part of every
+    /// chunk, in allocation order. This keeps the capacity of the vector.
+    fn chunk_slices_into(&mut self, slices: &mut Vec<NonNull<[T]>>) {
+        slices.clear();
+        if Self::IS_ZST {
+            let zst_len = self.inner.get_mut().zst_len;
+            if zst_len > 0 {
+                slices.push(NonNull::slice_from_raw_parts(NonNull::dangling(), zst_len));
+            }
+            return;
+        }
+        if let Some(arena) = self.inner_mut() {
+            arena.chunk_slices_into(slices);
+        }
+    }
+
+    /// Returns a value whose `Debug` implementation lists all elements, in allocation order.
+    ///
+    /// The `Debug` implementation of the arena itself only shows its size, because it can't
+    /// read the elements through a shared reference, see [`Arena::iter`].
+    pub fn debug_elements(&mut self) -> impl fmt::Debug + '_
+    where
+        T: fmt::Debug,
+    {
+        DebugElements(self.iter().collect())
+    }
+
+    /// Checks the internal invariants of the arena and returns a summary of its state.
+    ///
+    /// This is meant for sanity-checking the arena at checkpoints, e.g. in tests of unsafe
+    /// code built on top of it. It walks the whole chunk list.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an invariant is violated.
+    pub fn debug_validate(&mut self) -> ValidationReport {
+        let zst_len = self.inner.get_mut().zst_len;
+        match self.inner_mut() {
+            Some(arena) => {
+                assert!(!Self::IS_ZST, "chunks were allocated for a zero-sized type");
+                assert_eq!(zst_len, 0, "zero-sized elements in an arena of sized type");
+                arena.validate()
+            }
+            None => ValidationReport {
+                chunks: 0,
+                spare_chunks: 0,
+                len: zst_len,
+                free_slots_in_current_chunk: 0,
+            },
+        }
+    }
+
+    /// Clones all elements of this arena into `target`, in allocation order.
+    ///
+    /// The previous elements of `target` are dropped first, and its chunks are reused
+    /// before new ones are allocated, so cloning into an arena that is at least as large
+    /// does not allocate.
+    ///
+    /// This takes `&mut self` to guarantee that no reference handed out by [`Arena::alloc`]
+    /// is alive while the elements are read.
+    pub fn clone_into<B: Allocator>(&mut self, target: &mut Arena<N, T, B>)
+    where
+        T: Clone,
+    {
+        target.clear();
+        for chunk in self.chunk_slices() {
+            for elem in unsafe { chunk.as_ref() } {
+                target.alloc(elem.clone());
+            }
+        }
+    }
+
+    /// Returns a deep copy of the arena, with the elements cloned into new chunks in
+    /// allocation order.
+    ///
+    /// This is what `Clone` would do, but it takes `&mut self` for the same reason as
+    /// [`Arena::clone_into`].
+    pub fn snapshot(&mut self) -> Self
+    where
+        T: Clone,
+        A: Clone,
+    {
+        let mut copy = Arena::new_in(self.inner.get_mut().alloc.clone());
+        self.clone_into(&mut copy);
+        copy
+    }
+
+    /// Returns a marker for the current end of the arena, which [`Arena::rollback_to`] can
+    /// later return to.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let inner = self.inner.borrow();
+        if Self::IS_ZST {
+            return Checkpoint {
+                chunk: None,
+                len: inner.zst_len,
+            };
+        }
+        match unsafe { inner.typed::<T>() } {
+            Some(arena) => Checkpoint {
+                chunk: Some(unsafe { (*arena.head_chunk.as_ptr()).seq }),
+                len: arena.head_len(),
+            },
+            None => Checkpoint {
+                chunk: None,
+                len: 0,
+            },
+        }
+    }
+
+    /// Runs the closure with a [`Scope`] whose allocations are dropped again when the
+    /// closure returns or panics, and returns the result of the closure.
+    ///
+    /// Elements allocated before the scope stay in the arena. References to elements
+    /// allocated in the scope can't escape the closure:
+    ///
+    /// ```compile_fail
+    /// use linked_list_arena::Arena;
+    ///
+    /// let mut arena = Arena::<4, i32>::new();
+    /// let escaped = arena.scope(|scope| scope.alloc(1));
+    /// ```
+    pub fn scope<R>(&mut self, f: impl for<'s> FnOnce(&mut Scope<'s, N, T, A>) -> R) -> R {
+        f(&mut Scope::new(self))
+    }
+
+    /// Drops all elements that were allocated after the checkpoint was taken, and frees
+    /// the chunks that were added since then.
+    ///
+    /// Elements allocated before the checkpoint are not touched, so references to them stay
+    /// valid. This makes it possible to undo speculative allocations, e.g. when a parser
+    /// backtracks.
+    ///
+    /// # Safety
+    ///
+    /// - The checkpoint must have been taken from this arena.
+    /// - The arena must not have been rolled back to an earlier checkpoint, cleared,
+    ///   drained or appended to since the checkpoint was taken.
+    /// - No references to elements that were allocated after the checkpoint may be used
+    ///   afterwards.
+    ///
+    /// # Panics
+    ///
+    /// Panics without changing the arena if the checkpoint doesn't fit the chunks of the
+    /// arena anymore, because elements from before the checkpoint have been popped with
+    /// [`Arena::pop`].
+    pub unsafe fn rollback_to(&self, checkpoint: Checkpoint) {
+        if Self::IS_ZST {
+            let zst_len = &mut self.inner.borrow_mut().zst_len;
+            assert!(checkpoint.len <= *zst_len, "the checkpoint is out of date");
+            let zst_len = core::mem::replace(zst_len, checkpoint.len);
+            drop_zsts::<T>(zst_len - checkpoint.len);
+            return;
+        }
+        let mut inner = self.inner();
+        let Some(chunk) = checkpoint.chunk else {
+            // The arena had no chunks when the checkpoint was taken.
+            if let Some(arena) = inner.take() {
+                drop(inner);
+                let chunks = arena.unlink(&self.inner.borrow().alloc);
+                self.destroy_chunks(chunks);
+            }
+            return;
+        };
+        let arena = inner
+            .as_mut()
+            .expect("the checkpoint belongs to another arena");
+        // Check the checkpoint before anything is changed, so that a panic leaves the arena
+        // intact.
+        assert!(
+            arena.fits_checkpoint(chunk, checkpoint.len),
+            "the checkpoint is out of date or belongs to another arena"
+        );
+        let mut removed = Vec::new();
+        while (*arena.head_chunk.as_ptr()).seq != chunk {
+            // The head chunk only holds elements from after the checkpoint, so it may be
+            // borrowed mutably.
+            let head_mut = arena.head_chunk.as_mut();
+            let next_len = head_mut.next_len;
+            let next = head_mut.next.take();
+            let next = next.expect("the checkpoint belongs to another arena");
+            let head_len = arena.head_len();
+            let old_head = core::mem::replace(&mut arena.head_chunk, next);
+            removed.push((old_head, head_len));
+            arena.len_before_head -= next_len;
+            arena.chunk_count -= 1;
+            arena.capacity -= (*old_head.as_ptr()).capacity;
+            // The new head chunk may have borrowed elements, so no reference to it is
+            // created.
+            arena.ptr = Chunk::slots_ptr(arena.head_chunk).add(next_len);
+            arena.end = Chunk::end_ptr(arena.head_chunk);
+        }
+        let tail_len = arena.head_len() - checkpoint.len;
+        arena.ptr = arena.ptr.sub(tail_len);
+        let tail = NonNull::slice_from_raw_parts(arena.ptr.cast::<T>(), tail_len);
+        // The elements are dropped after the arena is released, so that their destructors
+        // could use it.
+        drop(inner);
+        self.destroy_chunks(removed);
+        core::ptr::drop_in_place(tail.as_ptr());
+    }
+
+    /// Drops the elements of unlinked chunks and frees the chunks.
+    ///
+    /// The arena must not be borrowed, so that the destructors of the elements could use it.
+    unsafe fn destroy_chunks(&self, chunks: Vec<(Link<N, T>, usize)>) {
+        for &(chunk, len) in &chunks {
+            let elems = Chunk::slots_ptr(chunk).cast::<T>().as_ptr();
+            core::ptr::drop_in_place(core::ptr::slice_from_raw_parts_mut(elems, len));
+        }
+        let alloc = &self.inner.borrow().alloc;
+        for (chunk, _) in chunks {
+            Chunk::free(chunk, alloc);
+        }
+    }
+
+    /// Moves the newest element out of the arena, or returns `None` if it is empty.
+    ///
+    /// The slot of the element is reused by the next allocation. Once the head chunk is
+    /// empty, it is freed and allocation continues in the previous chunk, so undoing
+    /// allocations, e.g. when a parser backtracks, gives the memory back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if elements have been pinned with [`Arena::alloc_pinned`].
+    pub fn pop(&mut self) -> Option<T> {
+        self.assert_not_pinned();
+        if Self::IS_ZST {
+            let zst_len = &mut self.inner.get_mut().zst_len;
+            *zst_len = zst_len.checked_sub(1)?;
+            return Some(unsafe { NonNull::<T>::dangling().read() });
+        }
+        let mut arena = self.inner_mut().take()?;
+        let alloc = &self.inner.get_mut().alloc;
+        // Only the head chunk can be empty, so at most one chunk has to be skipped.
+        arena.free_empty_head(alloc);
+        let elem = (arena.head_len() > 0).then(|| unsafe {
+            arena.ptr = arena.ptr.sub(1);
+            arena.ptr.cast::<T>().read()
+        });
+        arena.free_empty_head(alloc);
+        *self.inner_mut() = Some(arena);
+        elem
+    }
+
+    /// Drops all elements and keeps the chunks for reuse.
+    ///
+    /// The arena doesn't allocate any memory until it holds more elements than before.
+    /// Allocation starts over in the oldest chunk. If `T` doesn't need to be dropped, it
+    /// starts over in the newest chunk instead, and the chunks are not visited.
+    pub fn clear(&mut self) {
+        #[cfg(feature = "debug-backtrace")]
+        self.sites.get_mut().clear();
+        // The pinned elements are dropped in place, which ends their pinning.
+        self.inner.get_mut().pinned = false;
+        let zst_len = core::mem::take(&mut self.inner.get_mut().zst_len);
+        unsafe { drop_zsts::<T>(zst_len) };
+        if !core::mem::needs_drop::<T>() {
+            if let Some(arena) = self.inner_mut() {
+                arena.forget_elements();
+            }
+            return;
+        }
+        let Some(mut arena) = self.inner_mut().take() else {
+            return;
+        };
+        let mut spare = arena.spare.take();
+        let (chunk_count, capacity) = (arena.chunk_count, arena.capacity);
+        let chunks = arena.unlink(&self.inner.get_mut().alloc);
+        // The chunks are ordered from newest to oldest, so the oldest chunk ends up at the
+        // front of the spare list.
+        for (chunk, len) in chunks {
+            unsafe {
+                let elems = Chunk::slots_ptr(chunk).cast::<T>().as_ptr();
+                core::ptr::drop_in_place(core::ptr::slice_from_raw_parts_mut(elems, len));
+                (*chunk.as_ptr()).next = spare;
+            }
+            spare = Some(chunk);
+        }
+        // Make the first spare chunk the (empty) head chunk.
+        if let Some(head_chunk) = spare {
+            let seq = self.inner.get_mut().next_chunk_seq();
+            unsafe {
+                (*head_chunk.as_ptr()).seq = seq;
+                let spare = (*head_chunk.as_ptr()).next.take();
+                let ptr = Chunk::slots_ptr(head_chunk);
+                *self.inner_mut() = Some(InnerArena {
+                    head_chunk,
+                    ptr,
+                    end: Chunk::end_ptr(head_chunk),
+                    spare,
+                    len_before_head: 0,
+                    chunk_count,
+                    capacity,
+                });
+            }
+        }
+    }
+
+    /// Consumes the arena and turns it into a read-only [`FrozenArena`].
+    ///
+    /// The frozen arena allows indexed access and iteration through shared references,
+    /// and it can be shared between threads. The elements are not moved.
+    pub fn freeze(self) -> FrozenArena<N, T, A> {
+        let (inner, zst_len, alloc) = self.into_parts();
+        FrozenArena::from_inner(inner, zst_len, alloc)
+    }
+
+    /// Consumes the arena and returns an iterator that moves the elements out of it, in
+    /// allocation order.
+    ///
+    /// Each chunk is freed as soon as all of its elements have been yielded. Elements that
+    /// are not consumed are dropped together with the iterator.
+    ///
+    /// # Panics
+    ///
+    /// Panics if elements have been pinned with [`Arena::alloc_pinned`].
+    pub fn drain_destroy(mut self) -> DrainDestroy<N, T, A> {
+        self.assert_not_pinned();
+        let (inner, zst_len, alloc) = self.into_parts();
+        DrainDestroy {
+            chunks: inner.map_or_else(Vec::new, |arena| arena.unlink(&alloc)),
+            zst_len,
+            index: 0,
+            alloc,
+        }
+    }
+
+    /// Consumes the arena and destroys it, calling the destructor of all elements.
+    ///
+    /// The elements are dropped in reverse allocation order, so the newest element is
+    /// dropped first. This is the same as dropping the arena, but makes the point of
+    /// destruction explicit.
+    pub fn destroy(self) {
+        let (inner, zst_len, alloc) = self.into_parts();
+        if let Some(arena) = inner {
+            arena.destroy(&alloc);
+        }
+        unsafe { drop_zsts::<T>(zst_len) };
+    }
+}
+
+impl<const N: usize, T> InnerArena<N, T> {
+    /// Drops all elements in reverse allocation order and frees all chunks, which were
+    /// allocated by `alloc`.
+    fn destroy(self, alloc: &impl Allocator) {
+        free_chunks(self.spare, alloc);
+        // If the elements don't need to be dropped, we only have to free the chunks.
+        let drop_elems = core::mem::needs_drop::<T>();
+        // The chunks are linked from the newest to the oldest one, so only the elements
+        // within a chunk have to be visited backwards.
+        let mut cur_link = Some(self.head_chunk);
+        let mut len = self.head_len();
+        while let Some(chunk) = cur_link {
+            unsafe {
+                let start = Chunk::slots_ptr(chunk).cast::<T>();
+                if drop_elems {
+                    for i in (0..len).rev() {
+                        start.add(i).drop_in_place();
+                    }
+                }
+                cur_link = (*chunk.as_ptr()).next;
+                len = (*chunk.as_ptr()).next_len;
+                Chunk::free(chunk, alloc);
+            }
+        }
+    }
+
+    fn validate(&mut self) -> ValidationReport {
+        let start = unsafe { Chunk::slots_ptr(self.head_chunk) };
+        let end = unsafe { Chunk::end_ptr(self.head_chunk) };
+        assert!(
+            self.end == end,
+            "end pointer is not at the end of the head chunk"
+        );
+        assert!(
+            start <= self.ptr && self.ptr <= end,
+            "bump pointer is outside of the head chunk"
+        );
+        let head_len = unsafe { self.ptr.offset_from(start) as usize };
+
+        let mut chunks = 1;
+        let mut len = head_len;
+        let mut cur_chunk = unsafe { self.head_chunk.as_ref() };
+        let mut capacity = cur_chunk.capacity;
+        while let Some(next) = cur_chunk.next.map(|link| unsafe { link.as_ref() }) {
+            assert!(
+                0 < cur_chunk.next_len && cur_chunk.next_len <= next.capacity,
+                "length of a chunk is out of range"
+            );
+            chunks += 1;
+            len += cur_chunk.next_len;
+            capacity += next.capacity;
+            cur_chunk = next;
+        }
+        let spare_chunks = count_chunks(self.spare);
+        assert_eq!(
+            self.capacity,
+            capacity + count_slots(self.spare),
+            "tracked capacity is out of sync"
+        );
+        assert_eq!(
+            self.len_before_head + head_len,
+            len,
+            "tracked length is out of sync"
+        );
+        assert_eq!(
+            self.chunk_count,
+            chunks + spare_chunks,
+            "tracked number of chunks is out of sync"
+        );
+        ValidationReport {
+            chunks,
+            spare_chunks,
+            len,
+            free_slots_in_current_chunk: unsafe { end.offset_from(self.ptr) as usize },
+        }
+    }
+
+    /// Removes the first chunk from the spare list and returns it.
+    fn pop_spare(&mut self) -> Option<Link<N, T>> {
+        let mut chunk = self.spare.take()?;
+        self.spare = unsafe { chunk.as_mut() }.next.take();
+        Some(chunk)
+    }
+
+    /// Links the chunks of `other` in front of the chunks of this arena, so that its elements
+    /// come after the elements of this arena, and returns the combined arena.
+    fn append(mut self, mut other: Self) -> Self {
+        let len = self.len_before_head + self.head_len();
+        let mut top = Some(self.head_chunk);
+        let mut top_len = self.head_len();
+        if top_len == 0 {
+            // Only the head chunk may be empty, so an empty head chunk becomes a spare chunk.
+            unsafe {
+                let head = self.head_chunk.as_ptr();
+                top = (*head).next;
+                top_len = (*head).next_len;
+                (*head).next = self.spare.take();
+            }
+            self.spare = Some(self.head_chunk);
+        }
+        let mut oldest = other.head_chunk;
+        while let Some(next) = unsafe { (*oldest.as_ptr()).next } {
+            oldest = next;
+        }
+        unsafe {
+            (*oldest.as_ptr()).next = top;
+            (*oldest.as_ptr()).next_len = top_len;
+        }
+        while let Some(chunk) = self.pop_spare() {
+            unsafe { (*chunk.as_ptr()).next = other.spare.take() };
+            other.spare = Some(chunk);
+        }
+        other.len_before_head += len;
+        other.chunk_count += self.chunk_count;
+        other.capacity += self.capacity;
+        other
+    }
+
+    /// Empties the arena without dropping the elements, and keeps the chunks.
+    ///
+    /// The head chunk stays the head chunk, and the chunks behind it are put behind the
+    /// spare chunks. Only the spare chunks are visited, to find the end of their list.
+    fn forget_elements(&mut self) {
+        unsafe {
+            let head = self.head_chunk.as_ptr();
+            if let Some(rest) = (*head).next.take() {
+                match self.spare {
+                    Some(mut last) => {
+                        while let Some(next) = (*last.as_ptr()).next {
+                            last = next;
+                        }
+                        (*last.as_ptr()).next = Some(rest);
+                    }
+                    None => self.spare = Some(rest),
+                }
+            }
+            (*head).next_len = 0;
+            self.ptr = Chunk::slots_ptr(self.head_chunk);
+        }
+        self.len_before_head = 0;
+    }
+
+    /// Returns whether the chunk with the given sequence number is in the linked list and
+    /// holds at least `len` elements.
+    fn fits_checkpoint(&self, chunk: usize, len: usize) -> bool {
+        let mut cur_link = Some(self.head_chunk);
+        let mut chunk_len = self.head_len();
+        while let Some(link) = cur_link {
+            unsafe {
+                if (*link.as_ptr()).seq == chunk {
+                    return len <= chunk_len;
+                }
+                chunk_len = (*link.as_ptr()).next_len;
+                cur_link = (*link.as_ptr()).next;
+            }
+        }
+        false
+    }
+
+    /// Frees the head chunk if it is empty and not the only chunk, and continues allocation
+    /// after the last element of the previous chunk.
+    fn free_empty_head(&mut self, alloc: &impl Allocator) {
+        if self.head_len() > 0 {
+            return;
+        }
+        let head = self.head_chunk.as_ptr();
+        let Some(next) = (unsafe { (*head).next }) else {
+            return;
+        };
+        unsafe {
+            let next_len = (*head).next_len;
+            self.capacity -= (*head).capacity;
+            Chunk::free(self.head_chunk, alloc);
+            self.head_chunk = next;
+            self.len_before_head -= next_len;
+            self.chunk_count -= 1;
+            self.ptr = Chunk::slots_ptr(next).add(next_len);
+            self.end = Chunk::end_ptr(next);
+        }
+    }
+
+    /// Returns the number of initialized elements in the head chunk.
+    fn head_len(&self) -> usize {
+        unsafe { self.ptr.offset_from(Chunk::slots_ptr(self.head_chunk)) as usize }
+    }
+
+    /// Unlinks all chunks and returns them ordered from the newest to the oldest chunk,
+    /// each together with its number of initialized elements.
+    ///
+    /// The spare chunks are freed with `alloc`.
+    fn unlink(self, alloc: &impl Allocator) -> Vec<(Link<N, T>, usize)> {
+        let mut chunks = Vec::new();
+        let mut len = self.head_len();
+        free_chunks(self.spare, alloc);
+        let mut cur_link = Some(self.head_chunk);
+        while let Some(mut chunk) = cur_link {
+            let chunk_mut = unsafe { chunk.as_mut() };
+            cur_link = chunk_mut.next.take();
+            let next_len = chunk_mut.next_len;
+            chunks.push((chunk, len));
+            len = next_len;
+        }
+        chunks
+    }
+
+    /// Appends pointers to the initialized part of every chunk to `slices`, in allocation
+    /// order.
+    fn chunk_slices_into(&mut self, slices: &mut Vec<NonNull<[T]>>) {
+        let start = slices.len();
+        let mut len = self.head_len();
+        let mut cur_link = Some(self.head_chunk);
+        while let Some(link) = cur_link {
+            let start = unsafe { Chunk::slots_ptr(link) };
+            slices.push(NonNull::slice_from_raw_parts(start.cast::<T>(), len));
+            unsafe {
+                len = (*link.as_ptr()).next_len;
+                cur_link = (*link.as_ptr()).next;
+            }
+        }
+        slices[start..].reverse();
+    }
+}
+
+impl<const N: usize, A: Allocator> ErasedInner<N, A> {
+    fn new_in<T>(alloc: A) -> Self {
+        const {
+            assert!(
+                core::mem::size_of::<Option<InnerArena<N, T>>>()
+                    == core::mem::size_of::<Option<InnerArena<N, ()>>>()
+                    && core::mem::align_of::<Option<InnerArena<N, T>>>()
+                        == core::mem::align_of::<Option<InnerArena<N, ()>>>()
+            )
+        };
+        let mut inner = MaybeUninit::<Option<InnerArena<N, ()>>>::uninit();
+        unsafe {
+            inner
+                .as_mut_ptr()
+                .cast::<Option<InnerArena<N, T>>>()
+                .write(None)
+        };
+        ErasedInner {
+            inner,
+            zst_len: 0,
+            pinned: false,
+            chunk_seq: 0,
+            alloc,
+            drop_inner: drop_inner::<N, T, A>,
+        }
+    }
+
+    /// Returns the sequence number for a chunk that becomes the head chunk.
+    fn next_chunk_seq(&mut self) -> usize {
+        self.chunk_seq += 1;
+        self.chunk_seq
+    }
+
+    /// # Safety
+    ///
+    /// `T` must be the type that was passed to [`ErasedInner::new_in`].
+    unsafe fn typed<T>(&self) -> &Option<InnerArena<N, T>> {
+        &*self.inner.as_ptr().cast()
+    }
+
+    /// # Safety
+    ///
+    /// `T` must be the type that was passed to [`ErasedInner::new_in`].
+    unsafe fn typed_mut<T>(&mut self) -> &mut Option<InnerArena<N, T>> {
+        &mut *self.inner.as_mut_ptr().cast()
+    }
+}
+
+/// # Safety
+///
+/// `T` must be the type that was passed to [`ErasedInner::new_in`].
+unsafe fn drop_inner<const N: usize, T, A: Allocator>(erased: &mut ErasedInner<N, A>) {
+    if let Some(arena) = erased.typed_mut::<T>().take() {
+        arena.destroy(&erased.alloc);
+    }
+    drop_zsts::<T>(core::mem::take(&mut erased.zst_len));
+}
+
+/// Drops `len` zero-sized elements.
+///
+/// # Safety
+///
+/// If `len` is not zero, `T` must be zero-sized and the caller must own `len` values of it.
+unsafe fn drop_zsts<T>(len: usize) {
+    let elems = core::ptr::slice_from_raw_parts_mut(NonNull::<T>::dangling().as_ptr(), len);
+    core::ptr::drop_in_place(elems);
+}
+
+impl<const N: usize, A: Allocator> Drop for ErasedInner<N, A> {
+    fn drop(&mut self) {
+        unsafe { (self.drop_inner)(self) }
+    }
+}
+
+/// Counts the chunks in a linked list of chunks.
+fn count_chunks<const N: usize, T>(mut cur_link: Option<Link<N, T>>) -> usize {
+    let mut count = 0;
+    while let Some(chunk) = cur_link {
+        count += 1;
+        cur_link = unsafe { chunk.as_ref() }.next;
+    }
+    count
+}
+
+/// Counts the slots of all chunks in a linked list of chunks.
+fn count_slots<const N: usize, T>(mut cur_link: Option<Link<N, T>>) -> usize {
+    let mut count = 0;
+    while let Some(chunk) = cur_link {
+        let chunk = unsafe { chunk.as_ref() };
+        count += chunk.capacity;
+        cur_link = chunk.next;
+    }
+    count
+}
+
+/// Frees a linked list of chunks, which were allocated by `alloc`, without touching their
+/// slots.
+fn free_chunks<const N: usize, T>(mut cur_link: Option<Link<N, T>>, alloc: &impl Allocator) {
+    while let Some(chunk) = cur_link {
+        unsafe {
+            cur_link = chunk.as_ref().next;
+            Chunk::free(chunk, alloc);
+        }
+    }
+}
+
+/// A summary of the state of an arena, returned by [`Arena::debug_validate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// The number of chunks in the linked list.
+    pub chunks: usize,
+    /// The number of unused chunks that are kept for reuse.
+    pub spare_chunks: usize,
+    /// The number of initialized elements.
+    pub len: usize,
+    /// The number of free slots in the head chunk.
+    pub free_slots_in_current_chunk: usize,
+}
+
+/// The memory usage of an arena, returned by [`Arena::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArenaStats {
+    /// The number of chunks, including the ones kept for reuse.
+    pub chunks: usize,
+    /// The number of elements.
+    pub len: usize,
+    /// The number of bytes allocated for chunks.
+    pub allocated_bytes: usize,
+    /// The number of bytes occupied by the elements.
+    pub used_bytes: usize,
+    /// The number of bytes in the free slots of the head chunk.
+    pub free_bytes_in_current_chunk: usize,
+}
+
+/// An iterator that moves the elements out of an arena, created by
+/// [`Arena::drain_destroy`].
+pub struct DrainDestroy<const N: usize, T, A: Allocator = Global> {
+    /// The remaining chunks with their number of initialized elements, ordered from the
+    /// newest to the oldest chunk.
+    chunks: Vec<(Link<N, T>, usize)>,
+    /// The number of remaining elements if `T` is zero-sized.
+    zst_len: usize,
+    /// The index of the next element in the oldest remaining chunk.
+    index: usize,
+    /// The allocator that the chunks are freed with.
+    alloc: A,
+}
+
+// `DrainDestroy` owns the elements and the chunks, like a `Vec<T, A>`.
+unsafe impl<const N: usize, T: Send, A: Allocator + Send> Send for DrainDestroy<N, T, A> {}
+unsafe impl<const N: usize, T: Sync, A: Allocator + Sync> Sync for DrainDestroy<N, T, A> {}
+
+impl<const N: usize, T, A: Allocator> Iterator for DrainDestroy<N, T, A> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.zst_len > 0 {
+            self.zst_len -= 1;
+            return Some(unsafe { NonNull::dangling().read() });
+        }
+        loop {
+            let (chunk, len) = self.chunks.last_mut()?;
+            if self.index < *len {
+                let slot = unsafe { Chunk::slots_ptr(*chunk).add(self.index).as_mut() };
+                self.index += 1;
+                // Every initialized slot is read exactly once.
+                return Some(unsafe { slot.assume_init_read() });
+            }
+            // The oldest chunk has been drained, so we can free it.
+            let (chunk, _) = self.chunks.pop()?;
+            unsafe { Chunk::free(chunk, &self.alloc) };
+            self.index = 0;
+        }
+    }
+}
+
+impl<const N: usize, T, A: Allocator> Drop for DrainDestroy<N, T, A> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+/// A position in an arena, created by [`Arena::checkpoint`].
+#[derive(Clone, Copy, Debug)]
+pub struct Checkpoint {
+    /// The sequence number of the head chunk, or `None` if there was none.
+    chunk: Option<usize>,
+    /// The number of elements in the head chunk.
+    len: usize,
+}
+
+/// Allocates elements in an arena for a limited time, created by [`Arena::scope`].
+///
+/// When the scope ends, all elements allocated through it are dropped and the chunks that
+/// were added for them are freed.
+pub struct Scope<'s, const N: usize, T, A: Allocator = Global> {
+    arena: &'s Arena<N, T, A>,
+    checkpoint: Checkpoint,
+}
+
+impl<'s, const N: usize, T, A: Allocator> Scope<'s, N, T, A> {
+    fn new(arena: &'s Arena<N, T, A>) -> Self {
+        Scope {
+            arena,
+            checkpoint: arena.checkpoint(),
+        }
+    }
+
+    /// Allocates a new element for the rest of the scope, see [`Arena::alloc`].
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn alloc(&self, elem: T) -> &'s mut T {
+        self.arena.alloc(elem)
+    }
+
+    /// Allocates a new element for the rest of the scope, see [`Arena::alloc_with`].
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn alloc_with(&self, f: impl FnOnce() -> T) -> &'s mut T {
+        self.arena.alloc_with(f)
+    }
+
+    /// Allocates the elements of the iterator for the rest of the scope, see
+    /// [`Arena::alloc_extend`].
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn alloc_extend(&self, iter: impl IntoIterator<Item = T>) -> &'s mut [T] {
+        self.arena.alloc_extend(iter)
+    }
+
+    /// Runs the closure with a nested scope, see [`Arena::scope`].
+    ///
+    /// This scope can't be used while the nested one is alive, so everything the closure
+    /// allocates is dropped when it returns.
+    pub fn scope<R>(&mut self, f: impl for<'t> FnOnce(&mut Scope<'t, N, T, A>) -> R) -> R {
+        f(&mut Scope::new(self.arena))
+    }
+}
+
+impl<const N: usize, T, A: Allocator> Drop for Scope<'_, N, T, A> {
+    fn drop(&mut self) {
+        // References to the elements of the scope can't outlive the closure that received
+        // it, and the arena can't be cleared through a shared reference.
+        unsafe { self.arena.rollback_to(self.checkpoint) };
+    }
+}
+
+/// The error returned by [`Arena::try_alloc`] and [`Arena::try_alloc_with`] if a new chunk
+/// can't be allocated.
+///
+/// It holds the value that was passed in, so that it isn't lost.
+pub struct AllocError<T>(pub T);
+
+impl<T> AllocError<T> {
+    /// Returns the value that couldn't be allocated.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Debug for AllocError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AllocError { .. }")
+    }
+}
+
+impl<T> fmt::Display for AllocError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("failed to allocate a new chunk")
+    }
+}
+
+impl<T> core::error::Error for AllocError<T> {}
+
+/// A reserved but uninitialized slot in an arena, created by [`Arena::alloc_uninit`].
+pub struct UninitSlot<'a, const N: usize, T, A: Allocator = Global> {
+    arena: &'a Arena<N, T, A>,
+    /// The borrowed arena, which keeps other allocations from taking the slot. `None` if
+    /// `T` is zero-sized.
+    inner: Option<RefMut<'a, InnerArena<N, T>>>,
+    slot: NonNull<MaybeUninit<T>>,
+    /// The caller of [`Arena::alloc_uninit`], which is recorded once the slot is finished.
+    #[cfg(feature = "debug-backtrace")]
+    site: &'static Location<'static>,
+}
+
+impl<'a, const N: usize, T, A: Allocator> UninitSlot<'a, N, T, A> {
+    /// Initializes the slot with the given value and allocates the element.
+    pub fn write(mut self, elem: T) -> &'a mut T {
+        (*self).write(elem);
+        unsafe { self.assume_init() }
+    }
+
+    /// Allocates the element, assuming that the slot has been initialized.
+    ///
+    /// # Safety
+    ///
+    /// The slot must have been fully initialized with a valid value of `T`.
+    pub unsafe fn assume_init(self) -> &'a mut T {
+        let UninitSlot {
+            arena,
+            inner,
+            mut slot,
+            #[cfg(feature = "debug-backtrace")]
+            site,
+        } = self;
+        #[cfg(feature = "debug-backtrace")]
+        arena.record_site_at(site);
+        match inner {
+            Some(mut inner) => inner.ptr = slot.add(1),
+            None => {
+                arena.alloc_zsts(1);
+            }
+        }
+        slot.as_mut().assume_init_mut()
+    }
+}
+
+impl<const N: usize, T, A: Allocator> core::ops::Deref for UninitSlot<'_, N, T, A> {
+    type Target = MaybeUninit<T>;
+
+    fn deref(&self) -> &MaybeUninit<T> {
+        unsafe { self.slot.as_ref() }
+    }
+}
+
+impl<const N: usize, T, A: Allocator> core::ops::DerefMut for UninitSlot<'_, N, T, A> {
+    fn deref_mut(&mut self) -> &mut MaybeUninit<T> {
+        unsafe { self.slot.as_mut() }
+    }
+}
+
+/// A handle to an element that is being allocated by [`Arena::alloc_cyclic`].
+///
+/// The handle can be copied and stored before the element exists, and lives as long as
+/// the arena is borrowed.
+pub struct SelfRef<'a, T> {
+    ptr: NonNull<T>,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> SelfRef<'a, T> {
+    /// Returns a reference to the element.
+    ///
+    /// # Safety
+    ///
+    /// The element must have been allocated, i.e. the call to [`Arena::alloc_cyclic`] that
+    /// created the handle must have returned.
+    pub unsafe fn get(self) -> &'a T {
+        self.ptr.as_ref()
+    }
+
+    /// Returns a pointer to the slot of the element.
+    pub fn as_ptr(self) -> NonNull<T> {
+        self.ptr
+    }
+}
+
+impl<T> Clone for SelfRef<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SelfRef<'_, T> {}
+
+impl<T> fmt::Debug for SelfRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SelfRef").field(&self.ptr).finish()
+    }
+}
+
+/// An iterator over mutable references to the elements of an arena, created by
+/// [`Arena::iter_mut`].
+pub struct IterMut<'a, T> {
+    /// The chunks that haven't been visited yet, in allocation order.
+    chunks: alloc::vec::IntoIter<NonNull<[T]>>,
+    current: core::slice::IterMut<'a, T>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        loop {
+            if let Some(elem) = self.current.next() {
+                return Some(elem);
+            }
+            // The arena is borrowed mutably for `'a`, and every chunk is visited once.
+            self.current = unsafe { &mut *self.chunks.next()?.as_ptr() }.iter_mut();
+        }
+    }
+}
+
+impl<'a, const N: usize, T, A: Allocator> IntoIterator for &'a mut Arena<N, T, A> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+impl<const N: usize, T, A: Allocator + Default> Default for Arena<N, T, A> {
+    fn default() -> Self {
+        Self::new_in(A::default())
+    }
+}
+
+/// Allocates the elements one chunk at a time: the head chunk is looked up once, and then
+/// filled directly until it is full.
+impl<const N: usize, T, A: Allocator> Extend<T> for Arena<N, T, A> {
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        #[cfg(feature = "debug-backtrace")]
+        self.record_site();
+        let mut iter = iter.into_iter();
+        if Self::IS_ZST {
+            for elem in iter {
+                unsafe { self.alloc_zsts(1).write(elem) };
+            }
+            return;
+        }
+        self.reserve(iter.size_hint().0);
+        while let Some(first) = iter.next() {
+            let mut arena = self.inner_with_free_slots(1);
+            let free_slots = unsafe { arena.end.offset_from(arena.ptr) as usize };
+            for elem in core::iter::once(first).chain(iter.by_ref().take(free_slots - 1)) {
+                // The pointer is advanced after every element, so that a panicking
+                // iterator leaves the arena in a consistent state.
+                unsafe {
+                    arena.ptr.cast::<T>().write(elem);
+                    arena.ptr = arena.ptr.add(1);
+                }
+            }
+        }
+    }
+}
+
+impl<const N: usize, T, A: Allocator + Default> FromIterator<T> for Arena<N, T, A> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut arena = Self::default();
+        arena.extend(iter);
+        arena
+    }
+}
+
+/// Shows the number of elements and chunks, for any `T`. Use [`Arena::debug_elements`] to
+/// see the elements.
+impl<const N: usize, T, A: Allocator> fmt::Debug for Arena<N, T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Arena")
+            .field("len", &self.len())
+            .field("chunks", &self.chunk_count())
+            .finish()
+    }
+}
+
+/// The value returned by [`Arena::debug_elements`].
+struct DebugElements<'a, T>(Vec<&'a T>);
+
+impl<T: fmt::Debug> fmt::Debug for DebugElements<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.0).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    // The standard macro, not the `format!` of this crate that the glob import brings in.
+    use std::format;
+    use std::marker::PhantomPinned;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn empty_arena() {
+        let arena = Arena::<10, i32>::new();
+        assert!(arena.is_empty());
+    }
+
+    #[test]
+    fn free_slots() {
+        let arena = Arena::<10, i32>::new();
+        arena.alloc(1);
+        assert!(!arena.is_empty());
+        assert_eq!(arena.free_slots_in_current_chunk(), Some(9));
+    }
+
+    #[test]
+    fn deref_allocated_elements() {
+        let arena = Arena::<10, i32>::new();
+        let el1 = arena.alloc(2);
+        let el2 = arena.alloc(3);
+        assert_eq!(*el1, 2);
+        assert_eq!(*el2, 3);
+    }
+
+    #[test]
+    fn fill_chunk() {
+        let arena = Arena::<3, i32>::new();
+
+        arena.alloc(1);
+        assert_eq!(arena.free_slots_in_current_chunk(), Some(2));
+        let el1 = arena.alloc(2);
+        assert_eq!(arena.free_slots_in_current_chunk(), Some(1));
+        arena.alloc(3);
+        assert_eq!(arena.free_slots_in_current_chunk(), Some(0));
+        let el2 = arena.alloc(4);
+        assert_eq!(arena.free_slots_in_current_chunk(), Some(2));
+        arena.alloc(5);
+        assert_eq!(arena.free_slots_in_current_chunk(), Some(1));
+        assert_eq!(*el1, 2);
+        assert_eq!(*el2, 4);
+        *el2 = 6;
+        assert_eq!(*el1, 2);
+        assert_eq!(*el2, 6);
+        arena.alloc(6);
+        assert_eq!(arena.free_slots_in_current_chunk(), Some(0));
+        let el3 = arena.alloc(7);
+        assert_eq!(arena.free_slots_in_current_chunk(), Some(2));
+        assert_eq!(*el2, 6);
+        assert_eq!(*el3, 7);
+        arena.destroy();
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn data_structure_size() {
+        assert_eq!(std::mem::size_of::<usize>(), 8);
+        assert_eq!(std::mem::size_of::<InnerArena<1, i32>>(), 56);
+        #[cfg(not(feature = "debug-backtrace"))]
+        assert_eq!(std::mem::size_of::<Arena<1, i32>>(), 96);
+        assert_eq!(std::mem::size_of::<Chunk<100, i32>>(), 432);
+    }
+
+    struct CycleParticipant<'a> {
+        other: Cell<Option<&'a CycleParticipant<'a>>>,
+    }
+
+    #[test]
+    fn cycle() {
+        let arena: Arena<10, _> = Arena::new();
+
+        let a = arena.alloc(CycleParticipant {
+            other: Cell::new(None),
+        });
+        let b = arena.alloc(CycleParticipant {
+            other: Cell::new(None),
+        });
+
+        a.other.set(Some(b));
+        b.other.set(Some(a));
+    }
+
+    #[test]
+    fn map_in_place() {
+        let mut arena = Arena::<3, i32>::new();
+        arena.map_in_place(|_| unreachable!());
+        for i in 0..7 {
+            arena.alloc(i);
+        }
+        arena.map_in_place(|x| *x *= 10);
+        // The arena must still be usable after walking the chunks.
+        arena.alloc(70);
+        assert!(arena.freeze().iter().copied().eq((0..8).map(|x| x * 10)));
+    }
+
+    #[test]
+    fn try_map_in_place() {
+        let mut arena = Arena::<3, i32>::new();
+        for i in 0..7 {
+            arena.alloc(i);
+        }
+        let mut visited = Vec::new();
+        let result = arena.try_map_in_place(|x| {
+            visited.push(*x);
+            if *x == 4 {
+                return Err("four");
+            }
+            *x += 1;
+            Ok(())
+        });
+        assert_eq!(result, Err("four"));
+        assert_eq!(visited, [0, 1, 2, 3, 4]);
+        assert!(arena.freeze().iter().copied().eq([1, 2, 3, 4, 4, 5, 6]));
+    }
+
+    #[test]
+    fn iter_ptrs() {
+        let mut arena = Arena::<3, i32>::new();
+        assert_eq!(arena.iter_ptrs().count(), 0);
+        for i in 0..5 {
+            arena.alloc(i);
+        }
+        let ptrs = arena.iter_ptrs();
+        // Allocating while holding the iterator is fine; the new element is not yielded.
+        arena.alloc(5);
+        let ptrs: Vec<_> = ptrs.collect();
+        assert_eq!(ptrs.len(), 5);
+        for (i, ptr) in ptrs.into_iter().enumerate() {
+            unsafe {
+                assert_eq!(*ptr.as_ptr(), i as i32);
+                *ptr.as_ptr() += 10;
+            }
+        }
+        assert!(arena.freeze().iter().copied().eq([10, 11, 12, 13, 14, 5]));
+    }
+
+    struct SelfRef {
+        this: NonNull<SelfRef>,
+        _pin: PhantomPinned,
+    }
+
+    #[test]
+    fn alloc_with_addr() {
+        let arena = Arena::<2, SelfRef>::new();
+        for _ in 0..5 {
+            let elem = arena.alloc_with_addr(|this| SelfRef {
+                this,
+                _pin: PhantomPinned,
+            });
+            assert_eq!(elem.this, NonNull::from(&*elem));
+        }
+    }
+
+    #[test]
+    fn extend_and_collect() {
+        let mut arena: Arena<3, String> = (0..4).map(|i| i.to_string()).collect();
+        assert_eq!((arena.len(), arena.chunk_count()), (4, 2));
+        arena.alloc("4".to_string());
+        // A filter hides the length, so the chunks are allocated while filling.
+        arena.extend((5..12).filter(|_| true).map(|i| i.to_string()));
+        arena.extend(std::iter::empty());
+        assert_eq!((arena.len(), arena.chunk_count()), (12, 4));
+        assert_eq!(arena.chunk_lens(), [3, 3, 3, 3]);
+        assert!(arena.iter().cloned().eq((0..12).map(|i| i.to_string())));
+
+        let frozen: FrozenArena<2, i32> = (0..5).collect();
+        assert_eq!(frozen.get(4), Some(&4));
+        let arena: Arena<3, ()> = std::iter::repeat_n((), 5).collect();
+        assert_eq!(arena.len(), 5);
+    }
+
+    #[test]
+    fn extend_panics() {
+        let mut arena = Arena::<4, Rc<()>>::new();
+        let counter = Rc::new(());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            arena.extend((0..6).map(|i| {
+                assert!(i < 5);
+                Rc::clone(&counter)
+            }));
+        }));
+        assert!(result.is_err());
+        assert_eq!(arena.len(), 5);
+        drop(arena);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn scope() {
+        let mut arena = Arena::<2, Rc<()>>::new();
+        let counter = Rc::new(());
+        arena.alloc(Rc::clone(&counter));
+        let count = arena.scope(|scope| {
+            let first = scope.alloc(Rc::clone(&counter));
+            scope.alloc_extend([Rc::clone(&counter), Rc::clone(&counter)]);
+            let inner_count = scope.scope(|inner| {
+                for _ in 0..5 {
+                    inner.alloc(Rc::clone(&counter));
+                }
+                Rc::strong_count(&counter)
+            });
+            // The nested scope only dropped its own elements.
+            assert!(Rc::ptr_eq(first, &counter));
+            (inner_count, Rc::strong_count(&counter))
+        });
+        assert_eq!(count, (10, 5));
+        assert_eq!(Rc::strong_count(&counter), 2);
+        assert_eq!((arena.len(), arena.chunk_count()), (1, 1));
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            arena.scope(|scope| {
+                scope.alloc(Rc::clone(&counter));
+                panic!();
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(Rc::strong_count(&counter), 2);
+        assert_eq!(arena.len(), 1);
+    }
+
+    #[test]
+    fn pop() {
+        let mut arena = Arena::<3, String>::new();
+        for i in 0..7 {
+            arena.alloc(i.to_string());
+        }
+        assert_eq!(arena.chunk_count(), 3);
+        assert_eq!(arena.pop().as_deref(), Some("6"));
+        // The head chunk became empty and was freed.
+        assert_eq!(arena.chunk_count(), 2);
+        assert_eq!(arena.pop().as_deref(), Some("5"));
+        assert_eq!(arena.free_slots_in_current_chunk(), Some(1));
+        arena.alloc("a".to_string());
+        assert_eq!(arena.pop().as_deref(), Some("a"));
+        assert_eq!(arena.debug_validate().len, 5);
+        let rest: Vec<_> = std::iter::from_fn(|| arena.pop()).collect();
+        assert_eq!(rest, ["4", "3", "2", "1", "0"]);
+        assert_eq!((arena.len(), arena.chunk_count()), (0, 1));
+        arena.alloc("b".to_string());
+        assert_eq!(arena.iter().collect::<Vec<_>>(), ["b"]);
+
+        let mut arena = Arena::<3, ()>::new();
+        arena.alloc(());
+        assert_eq!(arena.pop(), Some(()));
+        assert_eq!(arena.pop(), None);
+    }
+
+    #[test]
+    #[should_panic = "the checkpoint is out of date"]
+    fn rollback_after_pop() {
+        let mut arena = Arena::<3, String>::new();
+        for i in 0..5 {
+            arena.alloc(i.to_string());
+        }
+        let checkpoint = arena.checkpoint();
+        arena.pop();
+        // The head chunk now holds fewer elements than when the checkpoint was taken.
+        unsafe { arena.rollback_to(checkpoint) };
+    }
+
+    #[test]
+    fn rollback_after_pop_and_alloc() {
+        let mut arena = Arena::<3, String>::new();
+        for i in 0..5 {
+            arena.alloc(i.to_string());
+        }
+        let checkpoint = arena.checkpoint();
+        arena.alloc("a".to_string());
+        arena.alloc("b".to_string());
+        for _ in 0..3 {
+            arena.pop();
+        }
+        arena.alloc("c".to_string());
+        arena.alloc("d".to_string());
+        unsafe { arena.rollback_to(checkpoint) };
+        assert!(arena
+            .iter_mut()
+            .map(|s| s.as_str())
+            .eq(["0", "1", "2", "3", "c"]));
+    }
+
+    #[test]
+    #[should_panic = "the checkpoint is out of date"]
+    fn rollback_after_chunk_is_freed() {
+        let mut arena = Arena::<2, String>::new();
+        for i in 0..3 {
+            arena.alloc(i.to_string());
+        }
+        let checkpoint = arena.checkpoint();
+        arena.pop();
+        arena.pop();
+        // The new chunk may get the address of the freed one.
+        arena.alloc("a".to_string());
+        arena.alloc("b".to_string());
+        unsafe { arena.rollback_to(checkpoint) };
+    }
+
+    #[test]
+    fn append() {
+        let counter = Rc::new(());
+        let mut arena = Arena::<3, Rc<()>>::new();
+        for _ in 0..4 {
+            arena.alloc(Rc::clone(&counter));
+        }
+        let other = Arena::<3, Rc<()>>::new();
+        let first = NonNull::from(&*other.alloc(Rc::new(())));
+        for _ in 0..3 {
+            other.alloc(Rc::clone(&counter));
+        }
+        arena.append(other);
+        let report = arena.debug_validate();
+        assert_eq!((report.len, report.chunks), (8, 4));
+        // The elements of the other arena were not moved.
+        assert_eq!(arena.iter().nth(4).map(NonNull::from), Some(first));
+        assert_eq!(Rc::strong_count(&counter), 8);
+
+        // An arena with an empty head chunk keeps it as a spare chunk.
+        let mut cleared = Arena::<3, Rc<()>>::new();
+        cleared.alloc(Rc::clone(&counter));
+        cleared.clear();
+        cleared.append(arena);
+        let report = cleared.debug_validate();
+        assert_eq!((report.len, report.chunks, report.spare_chunks), (8, 4, 1));
+        cleared.append(Arena::new());
+        assert_eq!(cleared.len(), 8);
+        while cleared.pop().is_some() {}
+        assert_eq!(Rc::strong_count(&counter), 1);
+
+        let mut zsts = Arena::<3, ()>::new();
+        zsts.alloc(());
+        zsts.append(std::iter::repeat_n((), 5).collect());
+        assert_eq!(zsts.len(), 6);
+    }
+
+    #[test]
+    fn destroy_in_reverse_order() {
+        struct Logged<'a>(usize, &'a RefCell<Vec<usize>>);
+
+        impl Drop for Logged<'_> {
+            fn drop(&mut self) {
+                self.1.borrow_mut().push(self.0);
+            }
+        }
+
+        let log = RefCell::new(Vec::new());
+        let arena = Arena::<3, Logged>::new();
+        arena.alloc(Logged(0, &log));
+        // This skips the free slots of the first chunk.
+        arena.alloc_extend([1, 2, 3].map(|i| Logged(i, &log)));
+        for i in 4..8 {
+            arena.alloc(Logged(i, &log));
+        }
+        arena.destroy();
+        assert_eq!(*log.borrow(), [7, 6, 5, 4, 3, 2, 1, 0]);
+
+        let arena = Arena::<3, Logged>::new();
+        for i in 0..5 {
+            arena.alloc(Logged(i, &log));
+        }
+        log.borrow_mut().clear();
+        drop(arena);
+        assert_eq!(*log.borrow(), [4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn get_by_index() {
+        let mut arena = Arena::<3, i32>::new();
+        assert_eq!(arena.get(0), None);
+        arena.alloc(0);
+        // This skips the free slots of the first chunk.
+        arena.alloc_extend([1, 2, 3]);
+        for i in 4..8 {
+            arena.alloc(i);
+        }
+        for i in 0..8 {
+            assert_eq!(arena.get(i), Some(&(i as i32)));
+        }
+        assert_eq!(arena.get(8), None);
+        *arena.get_mut(2).unwrap() = 20;
+        assert_eq!(arena.iter().nth(2), Some(&20));
+
+        let mut zsts = Arena::<3, ()>::new();
+        zsts.alloc(());
+        assert_eq!(zsts.get(0), Some(&()));
+        assert_eq!(zsts.get(1), None);
+    }
+
+    #[test]
+    #[should_panic = "the checkpoint is out of date"]
+    fn rollback_after_append() {
+        let mut arena = Arena::<3, String>::new();
+        arena.alloc("a".to_string());
+        arena.clear();
+        let checkpoint = arena.checkpoint();
+        // The empty head chunk becomes a spare chunk.
+        arena.append(std::iter::once("b".to_string()).collect());
+        unsafe { arena.rollback_to(checkpoint) };
+    }
+
+    #[test]
+    fn clear_without_drop_glue() {
+        let mut arena = Arena::<3, u32>::new();
+        arena.reserve(4);
+        for i in 0..7 {
+            arena.alloc(i);
+        }
+        let newest = arena.alloc(7) as *const u32;
+        arena.reserve(4);
+        arena.clear();
+        let report = arena.debug_validate();
+        assert_eq!((report.len, report.chunks, report.spare_chunks), (0, 1, 3));
+        // The newest chunk is reused first.
+        assert_eq!(arena.alloc(0) as *const u32, newest.wrapping_sub(1));
+        for i in 1..12 {
+            arena.alloc(i);
+        }
+        assert_eq!(arena.chunk_count(), 4);
+        assert!(arena.iter().copied().eq(0..12));
+    }
+
+    #[test]
+    fn alloc_raw() {
+        extern "C" fn increment(data: *mut std::ffi::c_void) {
+            unsafe { *data.cast::<i32>() += 1 };
+        }
+
+        let mut arena = Arena::<2, i32>::new();
+        let ptrs: Vec<NonNull<i32>> = (0..7).map(|i| arena.alloc_raw(i)).collect();
+        // The pointers stay valid while the arena grows and after iterating over it.
+        arena.alloc_extend([7, 8]);
+        arena.iter_mut().for_each(|x| *x *= 10);
+        for ptr in &ptrs {
+            increment(ptr.as_ptr().cast());
+        }
+        assert!(ptrs
+            .iter()
+            .map(|ptr| unsafe { *ptr.as_ref() })
+            .eq((0..7).map(|i| i * 10 + 1)));
+        assert!(arena
+            .iter()
+            .copied()
+            .eq([1, 11, 21, 31, 41, 51, 61, 70, 80]));
+
+        let arena = Arena::<2, ()>::new();
+        assert_eq!(arena.alloc_raw(()), NonNull::dangling());
+        assert_eq!(arena.len(), 1);
+    }
+
+    #[test]
+    fn alloc_cyclic() {
+        struct Node<'a> {
+            value: i32,
+            this: crate::SelfRef<'a, Node<'a>>,
+            next: Cell<Option<&'a Node<'a>>>,
+        }
+
+        let arena = Arena::<2, Node>::new();
+        let nodes: Vec<&Node> = (0..3)
+            .map(|value| {
+                arena.alloc_cyclic(|this| Node {
+                    value,
+                    this,
+                    next: Cell::new(None),
+                })
+            })
+            .collect();
+        for (node, next) in nodes.iter().zip(nodes.iter().cycle().skip(1)) {
+            node.next.set(Some(next));
+        }
+        for node in &nodes {
+            let this = unsafe { node.this.get() };
+            assert!(std::ptr::eq(this, *node));
+            assert_eq!(this.next.get().unwrap().value, (node.value + 1) % 3);
+        }
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut arena = Arena::<3, String>::new();
+        for i in 0..5 {
+            arena.alloc(i.to_string());
+        }
+        let mut copy = arena.snapshot();
+        arena.clear();
+        copy.alloc("5".to_string());
+        assert!(copy
+            .iter()
+            .map(String::as_str)
+            .eq(["0", "1", "2", "3", "4", "5"]));
+        assert!(arena.is_empty());
+    }
+
+    #[test]
+    fn debug() {
+        let mut arena = Arena::<3, i32>::new();
+        for i in 0..4 {
+            arena.alloc(i);
+        }
+        assert_eq!(format!("{arena:?}"), "Arena { len: 4, chunks: 2 }");
+        assert_eq!(format!("{:?}", arena.debug_elements()), "[0, 1, 2, 3]");
+
+        struct NotDebug(#[allow(dead_code)] u8);
+        let arena = Arena::<3, NotDebug>::new();
+        arena.alloc(NotDebug(0));
+        assert_eq!(format!("{arena:?}"), "Arena { len: 1, chunks: 1 }");
+    }
+
+    #[test]
+    fn alloc_pinned() {
+        use std::future::Future;
+        use std::task::{Context, Poll, Waker};
+
+        let arena = Arena::<2, _>::new();
+        let mut cx = Context::from_waker(Waker::noop());
+        for i in 0..5 {
+            let future = arena.alloc_pinned(async move { i * 2 });
+            assert_eq!(future.poll(&mut cx), Poll::Ready(i * 2));
+        }
+        assert_eq!(arena.len(), 5);
+    }
+
+    #[test]
+    fn pinned_elements_are_not_handed_out_mutably() {
+        let mut arena = Arena::<2, SelfRef>::new();
+        for _ in 0..3 {
+            let elem = arena.alloc_pinned(SelfRef {
+                this: NonNull::dangling(),
+                _pin: PhantomPinned,
+            });
+            let this = NonNull::from(&*elem);
+            unsafe { elem.get_unchecked_mut().this = this };
+        }
+        assert!(arena.iter().all(|elem| elem.this == NonNull::from(elem)));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            arena.iter_mut().next();
+        }));
+        assert!(result.is_err());
+        // Dropping the pinned elements ends their pinning.
+        arena.clear();
+        arena.alloc(SelfRef {
+            this: NonNull::dangling(),
+            _pin: PhantomPinned,
+        });
+        assert_eq!(arena.iter_mut().count(), 1);
+    }
+
+    #[test]
+    #[should_panic = "pinned"]
+    fn drain_pinned_elements() {
+        let arena = Arena::<2, i32>::new();
+        arena.alloc_pinned(1);
+        arena.drain_destroy();
+    }
+
+    #[test]
+    fn alloc_with_addr_panics() {
+        let arena = Arena::<2, i32>::new();
+        arena.alloc(1);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            arena.alloc_with_addr(|_| panic!());
+        }));
+        assert!(result.is_err());
+        // Allocating from inside the closure is not allowed.
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            arena.alloc_with_addr(|_| *arena.alloc(2));
+        }));
+        assert!(result.is_err());
+        assert_eq!(arena.free_slots_in_current_chunk(), Some(1));
+        arena.alloc(3);
+        assert!(arena.freeze().iter().copied().eq([1, 3]));
+    }
+
+    #[test]
+    fn alloc_with() {
+        let mut arena = Arena::<2, [u64; 64]>::new();
+        let a = arena.alloc_with(|| [1; 64]);
+        let b = arena.alloc_with(|| [2; 64]);
+        let c = arena.alloc_with(|| [3; 64]);
+        assert_eq!((a[0], b[63], c[10]), (1, 2, 3));
+        assert_eq!(arena.iter().count(), 3);
+    }
+
+    #[test]
+    fn alloc_uninit() {
+        struct Big {
+            id: u32,
+            data: [u8; 256],
+        }
+
+        let mut arena = Arena::<2, Big>::new();
+        let first = arena.alloc_uninit().write(Big {
+            id: 1,
+            data: [0; 256],
+        });
+        first.data[0] = 10;
+        let mut slot = arena.alloc_uninit();
+        let second = unsafe {
+            let ptr = slot.as_mut_ptr();
+            std::ptr::addr_of_mut!((*ptr).id).write(2);
+            std::ptr::addr_of_mut!((*ptr).data).write_bytes(7, 1);
+            slot.assume_init()
+        };
+        assert_eq!(second.data[255], 7);
+        // A slot that is never finished doesn't allocate anything.
+        drop(arena.alloc_uninit());
+        let ids: Vec<_> = arena.iter().map(|big| (big.id, big.data[0])).collect();
+        assert_eq!(ids, [(1, 10), (2, 7)]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn alloc_while_slot_is_alive() {
+        let arena = Arena::<2, i32>::new();
+        let _slot = arena.alloc_uninit();
+        arena.alloc(1);
+    }
+
+    #[test]
+    fn try_alloc() {
+        let mut arena = Arena::<2, String>::new();
+        for i in 0..5 {
+            assert_eq!(arena.try_alloc(i.to_string()).unwrap(), &i.to_string());
+        }
+        assert_eq!(arena.try_alloc_with(|| "5".into()).unwrap(), "5");
+        assert!(arena
+            .iter()
+            .map(String::as_str)
+            .eq(["0", "1", "2", "3", "4", "5"]));
+    }
+
+    #[cfg(all(target_pointer_width = "64", not(miri)))]
+    #[test]
+    fn try_alloc_out_of_memory() {
+        // A single chunk of this arena is larger than any address space.
+        let mut arena = Arena::<{ 1 << 50 }, u8>::new();
+        let err = arena.try_alloc(5).unwrap_err();
+        assert_eq!(err.to_string(), "failed to allocate a new chunk");
+        assert_eq!(err.into_inner(), 5);
+        let err = arena.try_alloc_with(|| 6).unwrap_err();
+        assert_eq!(err.into_inner()(), 6);
+        assert!(arena.is_empty());
+        assert_eq!(arena.debug_validate().chunks, 0);
+    }
+
+    /// An allocator that counts the chunks that are currently allocated from it.
+    #[derive(Default)]
+    struct CountingAlloc {
+        live: Cell<usize>,
+    }
+
+    unsafe impl Allocator for CountingAlloc {
+        fn allocate(
+            &self,
+            layout: Layout,
+        ) -> Result<NonNull<[u8]>, allocator_api2::alloc::AllocError> {
+            self.live.set(self.live.get() + 1);
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.live.set(self.live.get() - 1);
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    #[test]
+    fn custom_allocator() {
+        let alloc = CountingAlloc::default();
+        let arena = Arena::<3, String, _>::new_in(&alloc);
+        for i in 0..7 {
+            arena.alloc(i.to_string());
+        }
+        assert_eq!(alloc.live.get(), 3);
+        let checkpoint = arena.checkpoint();
+        arena.alloc("7".to_string());
+        arena.alloc("8".to_string());
+        unsafe { arena.rollback_to(checkpoint) };
+        assert_eq!(alloc.live.get(), 3);
+        drop(arena);
+        assert_eq!(alloc.live.get(), 0);
+
+        let arena = Arena::<3, i32, _>::new_in(&alloc);
+        for i in 0..7 {
+            arena.alloc(i);
+        }
+        let frozen = arena.freeze();
+        assert!(frozen.iter().copied().eq(0..7));
+        drop(frozen);
+        assert_eq!(alloc.live.get(), 0);
+
+        let arena = Arena::<3, i32, _>::new_in(&alloc);
+        for i in 0..7 {
+            arena.alloc(i);
+        }
+        let mut drain = arena.drain_destroy();
+        assert!(drain.by_ref().take(4).eq(0..4));
+        assert_eq!(alloc.live.get(), 2);
+        drop(drain);
+        assert_eq!(alloc.live.get(), 0);
+    }
+
+    #[test]
+    fn reserve() {
+        let alloc = CountingAlloc::default();
+        let arena = Arena::<3, i32, _>::with_capacity_in(7, &alloc);
+        assert_eq!(arena.chunk_count(), 3);
+        assert!(arena.is_empty());
+        for i in 0..9 {
+            arena.alloc(i);
+        }
+        assert_eq!(alloc.live.get(), 3);
+        arena.alloc(9);
+        assert_eq!(alloc.live.get(), 4);
+
+        // The free slots of the head chunk count towards the reservation.
+        arena.reserve(2);
+        assert_eq!(alloc.live.get(), 4);
+        arena.reserve(6);
+        assert_eq!(alloc.live.get(), 6);
+        for i in 10..16 {
+            arena.alloc(i);
+        }
+        assert_eq!(alloc.live.get(), 6);
+        let mut arena = arena;
+        assert_eq!(arena.debug_validate().len, 16);
+        assert!(arena.iter().copied().eq(0..16));
+    }
+
+    #[test]
+    fn stats() {
+        let arena = Arena::<4, u64>::new();
+        assert_eq!(arena.stats().allocated_bytes, 0);
+        assert!(arena.chunk_lens().is_empty());
+        let first = arena.alloc(0);
+        for i in 1..6 {
+            arena.alloc(i);
+        }
+        arena.alloc_extend([6, 7, 8]);
+        *first = 10;
+        assert_eq!(
+            arena.stats(),
+            ArenaStats {
+                chunks: 3,
+                len: 9,
+                allocated_bytes: 3 * std::mem::size_of::<Chunk<4, u64>>(),
+                used_bytes: 72,
+                free_bytes_in_current_chunk: 8,
+            }
+        );
+        assert_eq!(arena.chunk_lens(), [4, 2, 3]);
+        assert_eq!(*first, 10);
+    }
+
+    #[test]
+    fn failing_allocator() {
+        struct FailingAlloc;
+
+        unsafe impl Allocator for FailingAlloc {
+            fn allocate(
+                &self,
+                _: Layout,
+            ) -> Result<NonNull<[u8]>, allocator_api2::alloc::AllocError> {
+                Err(allocator_api2::alloc::AllocError)
+            }
+
+            unsafe fn deallocate(&self, _: NonNull<u8>, _: Layout) {
+                unreachable!("nothing was allocated");
+            }
+        }
+
+        let arena = Arena::<3, i32, _>::new_in(FailingAlloc);
+        assert_eq!(arena.try_alloc(5).unwrap_err().into_inner(), 5);
+        assert!(arena.is_empty());
+    }
+
+    #[test]
+    fn debug_validate() {
+        let mut arena = Arena::<3, i32>::new();
+        assert_eq!(
+            arena.debug_validate(),
+            ValidationReport {
+                chunks: 0,
+                spare_chunks: 0,
+                len: 0,
+                free_slots_in_current_chunk: 0
+            }
+        );
+        for i in 0..7 {
+            arena.alloc(i);
+        }
+        assert_eq!(
+            arena.debug_validate(),
+            ValidationReport {
+                chunks: 3,
+                spare_chunks: 0,
+                len: 7,
+                free_slots_in_current_chunk: 2
+            }
+        );
+        // The arena must still be usable after the check.
+        arena.alloc(7);
+        arena.alloc(8);
+        let report = arena.debug_validate();
+        assert_eq!((report.len, report.free_slots_in_current_chunk), (9, 0));
+        assert!(arena.freeze().iter().copied().eq(0..9));
+    }
+
+    #[test]
+    #[should_panic = "end pointer"]
+    fn debug_validate_detects_corruption() {
+        let mut arena = Arena::<3, i32>::new();
+        arena.alloc(1);
+        let inner = arena.inner_mut().as_mut().unwrap();
+        inner.end = inner.ptr;
+        arena.debug_validate();
+    }
+
+    #[test]
+    fn clone_into_reuses_chunks() {
+        let mut source = Arena::<3, String>::new();
+        for i in 0..5 {
+            source.alloc(i.to_string());
+        }
+        let mut target = Arena::<3, String>::new();
+        for i in 0..8 {
+            target.alloc(format!("old {i}"));
+        }
+        let old_ptrs: Vec<_> = target.iter_ptrs().collect();
+
+        source.clone_into(&mut target);
+        let report = target.debug_validate();
+        assert_eq!((report.chunks, report.spare_chunks, report.len), (2, 1, 5));
+        // The elements were placed in the chunks that were already there.
+        let new_ptrs: Vec<_> = target.iter_ptrs().collect();
+        assert_eq!(new_ptrs, old_ptrs[..5]);
+        assert!(target.freeze().iter().eq(["0", "1", "2", "3", "4"]));
+        source.destroy();
+    }
+
+    #[test]
+    fn clone_into_empty_arena() {
+        let mut source = Arena::<3, i32>::new();
+        let mut target = Arena::<3, i32>::new();
+        target.alloc(1);
+        source.clone_into(&mut target);
+        assert!(target.is_empty());
+        assert_eq!(target.free_slots_in_current_chunk(), Some(3));
+        source.alloc(2);
+        source.clone_into(&mut target);
+        assert!(!target.is_empty());
+        assert!(target.freeze().iter().copied().eq([2]));
+    }
+
+    #[test]
+    fn alloc_extend() {
+        let mut arena = Arena::<4, String>::new();
+        assert_eq!(arena.alloc_extend([]), [] as [String; 0]);
+        arena.alloc("a".to_string());
+        let bc = arena.alloc_extend(["b", "c"].map(String::from));
+        assert_eq!(bc, ["b", "c"]);
+        // This doesn't fit into the free slot of the first chunk.
+        let def = arena.alloc_extend(["d", "e", "f"].map(String::from));
+        def[0].push('!');
+        arena.alloc("g".to_string());
+        assert_eq!(arena.free_slots_in_current_chunk(), Some(0));
+        assert_eq!(
+            arena.debug_validate(),
+            ValidationReport {
+                chunks: 2,
+                spare_chunks: 0,
+                len: 7,
+                free_slots_in_current_chunk: 0
+            }
+        );
+        assert!(arena.iter().eq(["a", "b", "c", "d!", "e", "f", "g"]));
+
+        let mut drain = arena.drain_destroy();
+        assert_eq!(drain.nth(3).as_deref(), Some("d!"));
+        assert!(drain.eq(["e", "f", "g"]));
+    }
+
+    #[test]
+    fn alloc_extend_uses_arena() {
+        let arena = Arena::<4, i32>::new();
+        // The length of the iterator isn't known up front, so it is collected first.
+        let slice = arena.alloc_extend((1..4).filter(|&i| i > 0).map(|i| *arena.alloc(i * 10) + i));
+        assert_eq!(slice, [11, 22, 33]);
+        assert!(arena.freeze().iter().copied().eq([10, 20, 30, 11, 22, 33]));
+    }
+
+    #[test]
+    #[should_panic = "already mutably borrowed"]
+    fn alloc_extend_exact_size_uses_arena() {
+        let arena = Arena::<4, i32>::new();
+        arena.alloc_extend((1..4).map(|i| *arena.alloc(i)));
+    }
+
+    #[test]
+    fn alloc_extend_more_than_chunk_size() {
+        let mut arena = Arena::<3, String>::new();
+        arena.alloc(String::from("a"));
+        let slice = arena.alloc_extend((0..7).map(|i| i.to_string()));
+        assert_eq!(slice.len(), 7);
+        // The oversized chunk is full, so the next element goes into a new chunk.
+        arena.alloc(String::from("b"));
+        assert_eq!(arena.chunk_lens(), [1, 7, 1]);
+        assert_eq!(arena.capacity(), 3 + 7 + 3);
+        assert_eq!(
+            arena.allocated_bytes(),
+            3 * std::mem::size_of::<Chunk<3, String>>() + 4 * std::mem::size_of::<String>()
+        );
+        assert_eq!(arena.debug_validate().chunks, 3);
+        assert_eq!(arena.get(5).map(String::as_str), Some("4"));
+        assert_eq!(arena.pop().as_deref(), Some("b"));
+        assert_eq!(arena.pop().as_deref(), Some("6"));
+        arena.alloc(String::from("c"));
+        assert_eq!(arena.debug_validate().free_slots_in_current_chunk, 0);
+
+        // After clearing, the oversized chunk is reused like any other.
+        arena.clear();
+        for i in 0..13 {
+            arena.alloc(i.to_string());
+        }
+        assert_eq!(arena.chunk_count(), 3);
+        let frozen = arena.freeze();
+        assert!(frozen.iter().map(|s| s.parse::<i32>().unwrap()).eq(0..13));
+        assert_eq!(frozen.clone().as_chunk_slices().count(), 3);
+    }
+
+    #[test]
+    fn alloc_extend_more_than_chunk_size_into_empty_head() {
+        let mut arena = Arena::<4, i32>::with_capacity(1);
+        assert_eq!(arena.alloc_extend(0..10).len(), 10);
+        // The empty head chunk was too small and became a spare chunk.
+        assert_eq!(arena.debug_validate().spare_chunks, 1);
+        assert_eq!(arena.chunk_lens(), [10]);
+        arena.alloc(10);
+        assert_eq!(arena.chunk_lens(), [10, 1]);
+        assert_eq!(arena.chunk_count(), 2);
+
+        let mut arena = Arena::<4, i32>::new();
+        arena.reserve(1);
+        arena.alloc_extend(vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(arena.debug_validate().len, 6);
+        assert!(arena.iter().copied().eq(0..6));
+    }
+
+    #[test]
+    fn rollback_oversized_alloc_extend_into_empty_head() {
+        let mut arena = Arena::<4, String>::new();
+        arena.alloc(String::from("a"));
+        arena.clear();
+        let checkpoint = arena.checkpoint();
+        arena.alloc_extend((0..10).map(|i| i.to_string()));
+        unsafe { arena.rollback_to(checkpoint) };
+        assert_eq!(arena.len(), 0);
+        assert_eq!(arena.debug_validate().chunks, 1);
+    }
+
+    #[test]
+    fn alloc_slice_copy_more_than_chunk_size() {
+        let arena = Arena::<2, u8>::new();
+        assert_eq!(arena.alloc_slice_copy(b"hello"), b"hello");
+        assert!(arena.drain_destroy().eq(*b"hello"));
+    }
+
+    #[test]
+    fn alloc_extend_wrong_size_hint() {
+        struct TooShort(core::ops::Range<i32>);
+
+        impl Iterator for TooShort {
+            type Item = i32;
+
+            fn next(&mut self) -> Option<i32> {
+                self.0.next()
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (2, Some(2))
+            }
+        }
+
+        let arena = Arena::<4, i32>::new();
+        assert_eq!(arena.alloc_extend(TooShort(0..5)), [0, 1, 2, 3, 4]);
+        assert_eq!(arena.alloc_extend(TooShort(5..6)), [5]);
+        assert_eq!(arena.len(), 6);
+    }
+
+    #[test]
+    fn alloc_extend_panicking_iterator() {
+        let counter = Rc::new(());
+        let arena = Arena::<4, Rc<()>>::new();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            arena.alloc_extend((0..3).map(|i| {
+                assert!(i < 2);
+                Rc::clone(&counter)
+            }));
+        }));
+        assert!(result.is_err());
+        assert_eq!(Rc::strong_count(&counter), 1);
+        assert!(arena.is_empty());
+        arena.alloc(Rc::clone(&counter));
+    }
+
+    #[test]
+    fn alloc_slice_copy() {
+        let arena = Arena::<4, u8>::new();
+        arena.alloc(0);
+        let copy = arena.alloc_slice_copy(b"abcd");
+        copy[0] = b'A';
+        assert_eq!(copy, b"Abcd");
+        let frozen = arena.freeze();
+        assert_eq!(frozen.get(1), Some(&b'A'));
+        assert_eq!(frozen.position(|&c| c == b'c'), Some(3));
+        assert!(frozen.as_chunk_slices().eq([&[0][..], b"Abcd"]));
+    }
+
+    #[test]
+    fn drop_arena_with_gaps() {
+        let drop_counter = Arc::new(AtomicUsize::new(0));
+
+        let arena = Arena::<3, WithDrop>::new();
+        arena.alloc(WithDrop(0, Arc::clone(&drop_counter)));
+        arena.alloc(WithDrop(1, Arc::clone(&drop_counter)));
+        arena.alloc_extend((2..5).map(|i| WithDrop(i, Arc::clone(&drop_counter))));
+        arena.alloc_extend((5..7).map(|i| WithDrop(i, Arc::clone(&drop_counter))));
+        drop(arena);
+
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 7);
+    }
+
+    #[test]
+    fn iter() {
+        let mut arena = Arena::<3, i32>::new();
+        assert_eq!(arena.iter().next(), None);
+        for i in 0..7 {
+            arena.alloc(i);
+        }
+        assert!(arena.iter().copied().eq(0..7));
+
+        arena.iter_mut().for_each(|x| *x *= 2);
+        for x in &mut arena {
+            *x += 1;
+        }
+        // The arena must still be usable after iterating.
+        arena.alloc(15);
+        assert!(arena.iter().copied().eq((0..8).map(|x| x * 2 + 1)));
+    }
+
+    #[test]
+    fn zip_mut() {
+        let mut positions = Arena::<3, i32>::new();
+        let mut velocities = Arena::<4, i32>::new();
+        assert_eq!(positions.zip_mut(&mut velocities).count(), 0);
+        for i in 0..7 {
+            positions.alloc(i);
+            velocities.alloc(10 * i);
+        }
+        for (pos, vel) in positions.zip_mut(&mut velocities) {
+            *pos += *vel;
+        }
+        assert!(positions
+            .freeze()
+            .iter()
+            .copied()
+            .eq((0..7).map(|i| 11 * i)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn zip_mut_different_lengths() {
+        let mut a = Arena::<3, i32>::new();
+        let mut b = Arena::<3, i32>::new();
+        a.alloc(1);
+        let _ = a.zip_mut(&mut b);
+    }
+
+    #[cfg(feature = "debug-backtrace")]
+    #[test]
+    fn allocation_sites() {
+        let arena = Arena::<3, i32>::new();
+        for i in 0..5 {
+            arena.alloc(i);
+        }
+        let line = line!() + 1;
+        arena.alloc(5);
+
+        let sites = arena.allocation_sites();
+        assert_eq!(sites.len(), 2);
+        assert_eq!(sites[0].0.line(), line - 3);
+        assert_eq!(sites[0].1, 5);
+        assert_eq!(sites[1].0.line(), line);
+        assert_eq!(sites[1].0.file(), file!());
+        assert_eq!(sites[1].1, 1);
+    }
+
+    #[cfg(feature = "debug-backtrace")]
+    #[test]
+    fn allocation_sites_of_uninit_slots() {
+        let mut arena = Arena::<3, i32>::new();
+        drop(arena.alloc_uninit());
+        assert!(arena.allocation_sites().is_empty());
+        let line = line!() + 1;
+        let slot = arena.alloc_uninit();
+        slot.write(1);
+        let sites = arena.allocation_sites();
+        assert_eq!(sites.len(), 1);
+        assert_eq!(sites[0].0.line(), line);
+        assert_eq!(sites[0].1, 1);
+
+        arena.clear();
+        assert!(arena.allocation_sites().is_empty());
+    }
+
+    #[test]
+    fn drain_destroy_is_covariant() {
+        fn shorten<'a>(drain: DrainDestroy<4, &'static str>) -> DrainDestroy<4, &'a str> {
+            drain
+        }
+        let arena = Arena::<4, &'static str>::new();
+        arena.alloc("a");
+        assert!(shorten(arena.drain_destroy()).eq(["a"]));
+    }
+
+    #[test]
+    fn drain_destroy() {
+        assert_eq!(Arena::<3, i32>::new().drain_destroy().next(), None);
+
+        let arena = Arena::<3, String>::new();
+        for i in 0..7 {
+            arena.alloc(i.to_string());
+        }
+        let mut drain = arena.drain_destroy();
+        assert_eq!(drain.chunks.len(), 3);
+        assert_eq!(drain.next().as_deref(), Some("0"));
+        assert_eq!(drain.next().as_deref(), Some("1"));
+        assert_eq!(drain.next().as_deref(), Some("2"));
+        assert_eq!(drain.chunks.len(), 3);
+        assert_eq!(drain.next().as_deref(), Some("3"));
+        // The first chunk is freed once the iterator moves past it.
+        assert_eq!(drain.chunks.len(), 2);
+        assert!(drain.eq(["4", "5", "6"]));
+    }
+
+    struct WithDrop(i32, Arc<AtomicUsize>);
+
+    impl Drop for WithDrop {
+        fn drop(&mut self) {
+            self.1.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn drop_arena() {
+        let drop_counter = Arc::new(AtomicUsize::new(0));
+
+        let arena = Arena::<3, WithDrop>::new();
+        let el1 = arena.alloc(WithDrop(1, Arc::clone(&drop_counter)));
+        arena.alloc(WithDrop(2, Arc::clone(&drop_counter)));
+        arena.alloc(WithDrop(3, Arc::clone(&drop_counter)));
+        arena.alloc(WithDrop(4, Arc::clone(&drop_counter)));
+        arena.alloc(WithDrop(5, Arc::clone(&drop_counter)));
+        arena.alloc(WithDrop(6, Arc::clone(&drop_counter)));
+        arena.alloc(WithDrop(7, Arc::clone(&drop_counter)));
+        assert_eq!(el1.0, 1);
+        arena.destroy(); // Should be calling drop on all elements.
+
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 7);
+    }
+
+    #[test]
+    fn drop_without_destroy() {
+        let drop_counter = Arc::new(AtomicUsize::new(0));
+
+        let arena = Arena::<3, WithDrop>::new();
+        for i in 0..7 {
+            arena.alloc(WithDrop(i, Arc::clone(&drop_counter)));
+        }
+        drop(arena);
+
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 7);
+    }
+
+    #[test]
+    fn drop_arena_with_references_between_elements() {
+        let drop_counter = Arc::new(AtomicUsize::new(0));
+
+        // The elements don't use their references when dropped, so they may point into
+        // the arena that drops them.
+        struct Node<'a> {
+            next: Cell<Option<&'a Node<'a>>>,
+            _counter: WithDrop,
+        }
+
+        {
+            let arena = Arena::<2, Node>::new();
+            let new_node = |i| Node {
+                next: Cell::new(None),
+                _counter: WithDrop(i, Arc::clone(&drop_counter)),
+            };
+            let a = arena.alloc(new_node(1));
+            let b = arena.alloc(new_node(2));
+            let c = arena.alloc(new_node(3));
+            a.next.set(Some(b));
+            b.next.set(Some(c));
+            c.next.set(Some(a));
+        }
+
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn clear() {
+        let drop_counter = Arc::new(AtomicUsize::new(0));
+
+        let mut arena = Arena::<3, WithDrop>::new();
+        let first = NonNull::from(arena.alloc(WithDrop(0, Arc::clone(&drop_counter))));
+        for i in 1..7 {
+            arena.alloc(WithDrop(i, Arc::clone(&drop_counter)));
+        }
+        arena.clear();
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 7);
+        assert!(arena.is_empty());
+        let report = arena.debug_validate();
+        assert_eq!((report.chunks, report.spare_chunks, report.len), (1, 2, 0));
+
+        let new_first = NonNull::from(arena.alloc(WithDrop(0, Arc::clone(&drop_counter))));
+        assert_eq!(new_first, first);
+        for i in 1..9 {
+            arena.alloc(WithDrop(i, Arc::clone(&drop_counter)));
+        }
+        let report = arena.debug_validate();
+        assert_eq!((report.chunks, report.spare_chunks, report.len), (3, 0, 9));
+        drop(arena);
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 16);
+    }
+
+    #[test]
+    fn rollback() {
+        let drop_counter = Arc::new(AtomicUsize::new(0));
+
+        let mut arena = Arena::<3, WithDrop>::new();
+        let first = arena.alloc(WithDrop(0, Arc::clone(&drop_counter)));
+        arena.alloc(WithDrop(1, Arc::clone(&drop_counter)));
+        let checkpoint = arena.checkpoint();
+        for i in 2..6 {
+            arena.alloc(WithDrop(i, Arc::clone(&drop_counter)));
+        }
+        let inner_checkpoint = arena.checkpoint();
+        for i in 6..9 {
+            arena.alloc(WithDrop(i, Arc::clone(&drop_counter)));
+        }
+        unsafe { arena.rollback_to(inner_checkpoint) };
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 3);
+        unsafe { arena.rollback_to(checkpoint) };
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 7);
+        // Elements from before the checkpoint are still usable.
+        first.0 = 10;
+        arena.alloc(WithDrop(11, Arc::clone(&drop_counter)));
+        arena.alloc(WithDrop(12, Arc::clone(&drop_counter)));
+        assert!(arena.iter().map(|elem| elem.0).eq([10, 1, 11, 12]));
+        let report = arena.debug_validate();
+        assert_eq!((report.chunks, report.spare_chunks), (2, 0));
+
+        unsafe { arena.rollback_to(Arena::<3, WithDrop>::new().checkpoint()) };
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 11);
+        assert!(arena.is_empty());
+    }
+
+    #[test]
+    fn rollback_zero_sized_types() {
+        let mut arena = Arena::<3, ()>::new();
+        arena.alloc(());
+        let checkpoint = arena.checkpoint();
+        arena.alloc_extend([(), (), ()]);
+        unsafe { arena.rollback_to(checkpoint) };
+        assert_eq!(arena.iter().count(), 1);
+    }
+
+    #[test]
+    fn len_and_capacity() {
+        let mut arena = Arena::<3, i32>::new();
+        assert_eq!(
+            (arena.len(), arena.chunk_count(), arena.capacity()),
+            (0, 0, 0)
+        );
+        for i in 0..4 {
+            arena.alloc(i);
+        }
+        assert_eq!(
+            (arena.len(), arena.chunk_count(), arena.capacity()),
+            (4, 2, 6)
+        );
+        // The extended elements don't fit into the head chunk and leave a gap.
+        arena.alloc_extend([4, 5, 6]);
+        assert_eq!(
+            (arena.len(), arena.chunk_count(), arena.capacity()),
+            (7, 3, 9)
+        );
+        let checkpoint = arena.checkpoint();
+        arena.alloc_extend([7, 8]);
+        assert_eq!((arena.len(), arena.chunk_count()), (9, 4));
+        unsafe { arena.rollback_to(checkpoint) };
+        assert_eq!((arena.len(), arena.chunk_count()), (7, 3));
+        assert_eq!(arena.debug_validate().len, 7);
+        arena.clear();
+        assert_eq!(
+            (arena.len(), arena.chunk_count(), arena.capacity()),
+            (0, 3, 9)
+        );
+        for i in 0..8 {
+            arena.alloc(i);
+        }
+        assert_eq!((arena.len(), arena.chunk_count()), (8, 3));
+        assert_eq!(arena.debug_validate().len, 8);
+
+        let zsts = Arena::<3, ()>::new();
+        zsts.alloc_extend([(), ()]);
+        assert_eq!((zsts.len(), zsts.chunk_count()), (2, 0));
+        assert_eq!(zsts.capacity(), usize::MAX);
+    }
+
+    #[test]
+    fn zero_sized_types() {
+        let mut arena = Arena::<3, ()>::new();
+        assert!(arena.is_empty());
+        for _ in 0..5 {
+            arena.alloc(());
+        }
+        assert_eq!(arena.alloc_extend([(), ()]).len(), 2);
+        assert!(!arena.is_empty());
+        assert_eq!(arena.free_slots_in_current_chunk(), None);
+        assert_eq!(arena.iter().count(), 7);
+        assert_eq!(arena.debug_validate().len, 7);
+        assert_eq!(arena.debug_validate().chunks, 0);
+        assert_eq!(arena.drain_destroy().count(), 7);
+    }
+
+    #[test]
+    fn drop_zero_sized_types() {
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        struct Zst;
+
+        impl Drop for Zst {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let mut arena = Arena::<3, Zst>::new();
+        for _ in 0..4 {
+            arena.alloc(Zst);
+        }
+        arena.clear();
+        assert_eq!(DROPS.load(Ordering::SeqCst), 4);
+        assert!(arena.is_empty());
+        for _ in 0..5 {
+            arena.alloc(Zst);
+        }
+        let mut drain = arena.drain_destroy();
+        drain.next();
+        assert_eq!(DROPS.load(Ordering::SeqCst), 5);
+        drop(drain);
+        assert_eq!(DROPS.load(Ordering::SeqCst), 9);
+    }
+
+    #[test]
+    fn drop_partially_drained() {
+        let drop_counter = Arc::new(AtomicUsize::new(0));
+
+        let arena = Arena::<3, WithDrop>::new();
+        for i in 0..7 {
+            arena.alloc(WithDrop(i, Arc::clone(&drop_counter)));
+        }
+        let mut drain = arena.drain_destroy();
+        assert_eq!(drain.nth(3).unwrap().0, 3);
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 4);
+        drop(drain);
 
         assert_eq!(drop_counter.load(Ordering::SeqCst), 7);
     }
@@ -0,0 +1,417 @@
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::cell::{Cell, RefCell};
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+use allocator_api2::alloc::AllocError;
+
+use crate::{Allocator, Arena, Chunk, Global};
+
+/// An allocator that keeps the chunks freed by arenas, so that other arenas can reuse
+/// them.
+///
+/// Arenas created with [`Arena::new_in_pool`] take their chunks from the pool before new
+/// memory is allocated, and give them back to the pool instead of freeing them, e.g. when
+/// the arena is dropped. This helps if many short-lived arenas are created one after
+/// another. The chunks are only freed when the pool is dropped or [`ChunkPool::clear`] is
+/// called.
+///
+/// This also gives the stages of a pipeline their own arenas, while chunks are only
+/// allocated for the stage that needs the most of them. Each stage allocates into an arena
+/// from the shared pool, and when it is done, its arena gives the chunks back for the next
+/// stage:
+///
+/// ```
+/// use linked_list_arena::pool::ChunkPool;
+/// use linked_list_arena::Arena;
+///
+/// let pool = ChunkPool::<16, String>::new();
+/// let words = {
+///     let stage = Arena::new_in_pool(&pool);
+///     let words: Vec<&str> = "a pipeline of stages"
+///         .split(' ')
+///         .map(|word| stage.alloc(word.to_uppercase()).as_str())
+///         .collect();
+///     words.join(" ")
+/// };
+/// assert_eq!(pool.len(), 1);
+/// let stage = Arena::new_in_pool(&pool);
+/// stage.alloc(words);
+/// assert!(pool.is_empty());
+/// ```
+///
+/// Memory that doesn't have the layout of a chunk is passed on to the global allocator.
+pub struct ChunkPool<const N: usize, T> {
+    /// The newest free chunk, which contains all other free chunks in its linked list.
+    free: Cell<Option<NonNull<FreeChunk>>>,
+    /// The number of free chunks.
+    len: Cell<usize>,
+    /// The pool only stores memory for chunks of `T`, never a `T` itself.
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// The start of a free chunk, which links it to the next free chunk.
+struct FreeChunk {
+    next: Option<NonNull<FreeChunk>>,
+}
+
+impl<const N: usize, T> ChunkPool<N, T> {
+    const CHUNK_LAYOUT: Layout = Layout::new::<Chunk<N, T>>();
+
+    /// Creates a new, empty pool.
+    /// This function does not allocate any memory.
+    pub fn new() -> Self {
+        ChunkPool {
+            free: Cell::new(None),
+            len: Cell::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the number of free chunks in the pool.
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Frees all chunks in the pool.
+    ///
+    /// Chunks that are still used by arenas are given back to the pool later.
+    pub fn clear(&self) {
+        while let Some(chunk) = self.free.get() {
+            unsafe {
+                self.free.set((*chunk.as_ptr()).next);
+                Global.deallocate(chunk.cast(), Self::CHUNK_LAYOUT);
+            }
+        }
+        self.len.set(0);
+    }
+}
+
+unsafe impl<const N: usize, T> Allocator for ChunkPool<N, T> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout != Self::CHUNK_LAYOUT {
+            return Global.allocate(layout);
+        }
+        let Some(chunk) = self.free.get() else {
+            return Global.allocate(layout);
+        };
+        self.free.set(unsafe { (*chunk.as_ptr()).next });
+        self.len.set(self.len.get() - 1);
+        Ok(NonNull::slice_from_raw_parts(chunk.cast(), layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout != Self::CHUNK_LAYOUT {
+            return Global.deallocate(ptr, layout);
+        }
+        // A chunk holds a link to the next chunk itself, so it is large enough and
+        // sufficiently aligned for the link of a free chunk.
+        let chunk = ptr.cast::<FreeChunk>();
+        chunk.write(FreeChunk {
+            next: self.free.get(),
+        });
+        self.free.set(Some(chunk));
+        self.len.set(self.len.get() + 1);
+    }
+}
+
+impl<const N: usize, T> Default for ChunkPool<N, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, T> Drop for ChunkPool<N, T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<'p, const N: usize, T> Arena<N, T, &'p ChunkPool<N, T>> {
+    /// Creates a new arena that takes its chunks from the pool, and gives them back to it
+    /// instead of freeing them.
+    /// This function does not allocate any memory.
+    pub fn new_in_pool(pool: &'p ChunkPool<N, T>) -> Self {
+        Self::new_in(pool)
+    }
+
+    /// Creates a [`Batch`] that allocates from the pool of this arena.
+    /// This function does not allocate any memory.
+    pub fn batch(&self) -> Batch<'p, N, T> {
+        Batch {
+            arena: Arena::new_in_pool(self.inner.borrow().alloc),
+        }
+    }
+}
+
+/// Allocations that share the chunk pool of an arena, but are dropped independently of its
+/// other elements, created by [`Arena::batch`].
+///
+/// The elements of a batch are allocated in chunks of their own. When the batch is dropped,
+/// its elements are dropped and its chunks go back to the pool, where the arena and other
+/// batches reuse them. This suits a few large temporary structures next to long-lived
+/// elements:
+///
+/// ```
+/// use linked_list_arena::pool::ChunkPool;
+/// use linked_list_arena::Arena;
+///
+/// let pool = ChunkPool::<4, String>::new();
+/// let arena = Arena::new_in_pool(&pool);
+/// let name = arena.alloc(String::from("long-lived"));
+/// {
+///     let batch = arena.batch();
+///     for i in 0..10 {
+///         batch.alloc(format!("temporary {i}"));
+///     }
+/// }
+/// assert_eq!(pool.len(), 3);
+/// // The arena continues in the chunks of the batch.
+/// for i in 0..11 {
+///     arena.alloc(i.to_string());
+/// }
+/// assert_eq!(pool.len(), 1);
+/// assert_eq!(name, "long-lived");
+/// ```
+pub struct Batch<'p, const N: usize, T> {
+    arena: Arena<N, T, &'p ChunkPool<N, T>>,
+}
+
+impl<const N: usize, T> Batch<'_, N, T> {
+    /// Allocates a new element in the batch, see [`Arena::alloc`].
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn alloc(&self, elem: T) -> &mut T {
+        self.arena.alloc(elem)
+    }
+
+    /// Allocates a new element in the batch, see [`Arena::alloc_with`].
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn alloc_with(&self, f: impl FnOnce() -> T) -> &mut T {
+        self.arena.alloc_with(f)
+    }
+
+    /// Allocates the elements of the iterator in the batch, see [`Arena::alloc_extend`].
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "debug-backtrace", track_caller)]
+    pub fn alloc_extend(&self, iter: impl IntoIterator<Item = T>) -> &mut [T] {
+        self.arena.alloc_extend(iter)
+    }
+
+    /// Returns the number of elements in the batch.
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arena.is_empty()
+    }
+}
+
+/// A bounded pool of arenas that are cleared and reused instead of dropped.
+///
+/// A request handler checks out an arena, allocates into it and checks it back in. Since
+/// [`Arena::clear`] keeps the chunks of an arena, the next handler that checks it out
+/// allocates into memory that is already there. At most `max_arenas` arenas are kept,
+/// further arenas are dropped when they are checked in.
+///
+/// Arenas can't be sent to other threads, so a multi-threaded server needs one pool per
+/// thread, e.g. in a `thread_local!`.
+pub struct ArenaPool<const N: usize, T> {
+    arenas: RefCell<Vec<Arena<N, T>>>,
+    max_arenas: usize,
+}
+
+impl<const N: usize, T> ArenaPool<N, T> {
+    /// Creates a new, empty pool that keeps at most `max_arenas` arenas.
+    /// This function does not allocate any memory.
+    pub fn new(max_arenas: usize) -> Self {
+        ArenaPool {
+            arenas: RefCell::new(Vec::new()),
+            max_arenas,
+        }
+    }
+
+    /// Creates a new pool with `max_arenas` arenas, each with room for `capacity` elements.
+    pub fn new_prewarmed(max_arenas: usize, capacity: usize) -> Self {
+        let arenas = (0..max_arenas)
+            .map(|_| Arena::with_capacity(capacity))
+            .collect();
+        ArenaPool {
+            arenas: RefCell::new(arenas),
+            max_arenas,
+        }
+    }
+
+    /// Takes an arena out of the pool, or creates a new one if the pool is empty.
+    ///
+    /// The arena is empty, but keeps the chunks it had when it was checked in.
+    pub fn checkout(&self) -> Arena<N, T> {
+        self.arenas.borrow_mut().pop().unwrap_or_default()
+    }
+
+    /// Clears the arena and puts it back into the pool. If the pool is full, the arena is
+    /// dropped instead.
+    pub fn checkin(&self, mut arena: Arena<N, T>) {
+        if self.len() >= self.max_arenas {
+            return;
+        }
+        // The destructors of the elements may use the pool, so it isn't borrowed yet.
+        arena.clear();
+        let mut arenas = self.arenas.borrow_mut();
+        if arenas.len() < self.max_arenas {
+            arenas.push(arena);
+        }
+    }
+
+    /// Returns the number of arenas in the pool.
+    pub fn len(&self) -> usize {
+        self.arenas.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn reuse_chunks() {
+        let pool = ChunkPool::<4, String>::new();
+        let arena = Arena::new_in_pool(&pool);
+        let first: *const String = arena.alloc(String::from("a"));
+        for i in 0..10 {
+            arena.alloc(i.to_string());
+        }
+        assert!(pool.is_empty());
+        drop(arena);
+        assert_eq!(pool.len(), 3);
+
+        let arena = Arena::new_in_pool(&pool);
+        // The newest free chunk is the oldest chunk of the previous arena.
+        let reused: *const String = arena.alloc(String::from("b"));
+        assert_eq!(reused, first);
+        assert_eq!(pool.len(), 2);
+        let frozen = arena.freeze();
+        assert_eq!(frozen[0], "b");
+        drop(frozen);
+        assert_eq!(pool.len(), 3);
+        pool.clear();
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn pipeline_stages() {
+        let pool = ChunkPool::<4, String>::new();
+        let mut input: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+        for stage in 0..3 {
+            let arena = Arena::new_in_pool(&pool);
+            for elem in &input {
+                arena.alloc(format!("{elem}{stage}"));
+            }
+            input = arena.drain_destroy().collect();
+            // The chunks of each stage go back to the pool, so only the first stage
+            // allocates any.
+            assert_eq!(pool.len(), 3);
+        }
+        assert_eq!(input[9], "9012");
+    }
+
+    #[test]
+    fn drop_batches_independently() {
+        let counter = Rc::new(());
+        let pool = ChunkPool::<2, Rc<()>>::new();
+        let arena = Arena::new_in_pool(&pool);
+        arena.alloc(Rc::clone(&counter));
+        let first = arena.batch();
+        let second = arena.batch();
+        first.alloc_extend([Rc::clone(&counter), Rc::clone(&counter)]);
+        first.alloc_extend([Rc::clone(&counter), Rc::clone(&counter)]);
+        second.alloc(Rc::clone(&counter));
+        assert_eq!((first.len(), second.len()), (4, 1));
+        assert_eq!(Rc::strong_count(&counter), 7);
+
+        drop(first);
+        assert_eq!(Rc::strong_count(&counter), 3);
+        assert_eq!(pool.len(), 2);
+        // The arena and the other batch reuse the chunks of the dropped batch.
+        second.alloc_with(|| Rc::clone(&counter));
+        second.alloc(Rc::clone(&counter));
+        arena.alloc(Rc::clone(&counter));
+        arena.alloc(Rc::clone(&counter));
+        assert!(pool.is_empty());
+        assert_eq!(arena.len(), 3);
+
+        drop(second);
+        assert_eq!(pool.len(), 2);
+        drop(arena);
+        assert_eq!(Rc::strong_count(&counter), 1);
+        assert_eq!(pool.len(), 4);
+    }
+
+    #[test]
+    fn checkout_and_checkin() {
+        let counter = Rc::new(());
+        let pool = ArenaPool::<4, Rc<()>>::new(1);
+        assert!(pool.is_empty());
+        let arena = pool.checkout();
+        let other = pool.checkout();
+        for _ in 0..6 {
+            arena.alloc(Rc::clone(&counter));
+            other.alloc(Rc::clone(&counter));
+        }
+        pool.checkin(arena);
+        assert_eq!(pool.len(), 1);
+        assert_eq!(Rc::strong_count(&counter), 7);
+        // The pool is full, so the second arena is dropped.
+        pool.checkin(other);
+        assert_eq!(pool.len(), 1);
+        assert_eq!(Rc::strong_count(&counter), 1);
+
+        let arena = pool.checkout();
+        assert!(pool.is_empty());
+        assert!(arena.is_empty());
+        assert_eq!(arena.capacity(), 8);
+    }
+
+    #[test]
+    fn prewarmed_arenas() {
+        let pool = ArenaPool::<4, i32>::new_prewarmed(2, 10);
+        assert_eq!(pool.len(), 2);
+        let arena = pool.checkout();
+        assert_eq!(arena.capacity(), 12);
+        arena.alloc(1);
+        pool.checkin(arena);
+        assert_eq!(pool.checkout().capacity(), 12);
+    }
+
+    #[test]
+    fn drop_elements_of_reused_chunks() {
+        let counter = Rc::new(());
+        let pool = ChunkPool::<2, Rc<()>>::new();
+        for _ in 0..3 {
+            let arena = Arena::new_in_pool(&pool);
+            for _ in 0..5 {
+                arena.alloc(Rc::clone(&counter));
+            }
+            let checkpoint = arena.checkpoint();
+            arena.alloc(Rc::clone(&counter));
+            arena.alloc(Rc::clone(&counter));
+            unsafe { arena.rollback_to(checkpoint) };
+            assert_eq!(Rc::strong_count(&counter), 6);
+        }
+        assert_eq!(Rc::strong_count(&counter), 1);
+        assert_eq!(pool.len(), 4);
+    }
+}
@@ -0,0 +1,81 @@
+use alloc::vec::Vec;
+
+use rayon::prelude::*;
+
+use crate::frozen::FrozenArena;
+use crate::{Allocator, Arena};
+
+// The work is split chunk by chunk: every chunk is a slice that rayon can hand to a
+// different thread, and the elements of a chunk are visited sequentially.
+
+impl<const N: usize, T: Sync, A: Allocator> Arena<N, T, A> {
+    /// Returns a parallel iterator over all elements.
+    ///
+    /// Like [`Arena::iter`], this takes `&mut self` so that no reference handed out by
+    /// [`Arena::alloc`] is alive while iterating.
+    pub fn par_iter(&mut self) -> impl ParallelIterator<Item = &T> + '_ {
+        let chunks: Vec<&[T]> = self
+            .chunk_slices()
+            .into_iter()
+            .map(|chunk| unsafe { &*chunk.as_ptr() })
+            .collect();
+        chunks.into_par_iter().flat_map_iter(|chunk| chunk.iter())
+    }
+}
+
+impl<const N: usize, T: Send, A: Allocator> Arena<N, T, A> {
+    /// Returns a parallel iterator over mutable references to all elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if elements have been pinned with [`Arena::alloc_pinned`].
+    pub fn par_iter_mut(&mut self) -> impl ParallelIterator<Item = &mut T> + '_ {
+        self.assert_not_pinned();
+        // The arena is borrowed mutably, and the chunks don't overlap.
+        let chunks: Vec<&mut [T]> = self
+            .chunk_slices()
+            .into_iter()
+            .map(|chunk| unsafe { &mut *chunk.as_ptr() })
+            .collect();
+        chunks
+            .into_par_iter()
+            .flat_map_iter(|chunk| chunk.iter_mut())
+    }
+}
+
+impl<const N: usize, T: Sync, A: Allocator> FrozenArena<N, T, A> {
+    /// Returns a parallel iterator over all elements.
+    pub fn par_iter(&self) -> impl ParallelIterator<Item = &T> + '_ {
+        let chunks: Vec<&[T]> = self.as_chunk_slices().collect();
+        chunks.into_par_iter().flat_map_iter(|chunk| chunk.iter())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rayon::prelude::*;
+
+    use crate::Arena;
+
+    #[test]
+    fn par_iter() {
+        let mut arena = Arena::<16, u64>::new();
+        for i in 0..1000 {
+            arena.alloc(i);
+        }
+        assert_eq!(arena.par_iter().sum::<u64>(), 499_500);
+        arena.par_iter_mut().for_each(|x| *x *= 2);
+        let frozen = arena.freeze();
+        assert_eq!(frozen.par_iter().max(), Some(&1998));
+        assert!(frozen.iter().copied().eq((0..1000).map(|x| x * 2)));
+    }
+
+    #[test]
+    fn zero_sized_types() {
+        let mut arena = Arena::<16, ()>::new();
+        for _ in 0..100 {
+            arena.alloc(());
+        }
+        assert_eq!(arena.par_iter_mut().count(), 100);
+    }
+}
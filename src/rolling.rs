@@ -0,0 +1,173 @@
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use core::mem::MaybeUninit;
+
+/// A bounded arena that keeps at most `max_chunks` chunks of `N` elements each.
+///
+/// Once all chunks are full, allocating a new element drops the elements of the oldest
+/// chunk and reuses its memory for the newest elements, like a ring buffer of chunks.
+///
+/// Because allocating can drop older elements, [`RollingArena::push`] takes `&mut self`
+/// and the returned reference does not outlive the next allocation.
+pub struct RollingArena<const N: usize, T> {
+    /// The chunks from oldest to newest. All chunks except the last one are full.
+    list: VecDeque<Box<[MaybeUninit<T>; N]>>,
+    /// The number of initialized elements in the last chunk.
+    back_len: usize,
+    max_chunks: usize,
+}
+
+impl<const N: usize, T> RollingArena<N, T> {
+    /// Creates a new arena that keeps at most `max_chunks` chunks.
+    /// This function does not allocate any memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_chunks` is zero.
+    pub fn new(max_chunks: usize) -> Self {
+        const { crate::check_chunk_size::<N, T>() };
+        assert!(max_chunks != 0, "RollingArena needs at least one chunk");
+        RollingArena {
+            list: VecDeque::new(),
+            back_len: 0,
+            max_chunks,
+        }
+    }
+
+    /// Allocates a new element in the arena and returns a mutable reference to it.
+    ///
+    /// If all `max_chunks` chunks are full, the elements of the oldest chunk are dropped
+    /// first.
+    pub fn push(&mut self, elem: T) -> &mut T {
+        if self.list.is_empty() || self.back_len == N {
+            if self.list.len() < self.max_chunks {
+                self.list.push_back(crate::new_uninit_slots());
+            } else {
+                // Move the oldest chunk to the back without reallocating it.
+                // The chunk is detached first, so a panicking destructor can't lead to its
+                // elements being dropped again.
+                let mut oldest = self.list.pop_front().unwrap();
+                unsafe { core::ptr::drop_in_place(oldest.as_mut_ptr().cast::<[T; N]>()) };
+                self.list.push_back(oldest);
+            }
+            self.back_len = 0;
+        }
+        let slot = &mut self.list.back_mut().unwrap()[self.back_len];
+        self.back_len += 1;
+        slot.write(elem)
+    }
+
+    /// Returns the number of elements in the arena.
+    pub fn len(&self) -> usize {
+        match self.list.len() {
+            0 => 0,
+            chunks => (chunks - 1) * N + self.back_len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of chunks the arena keeps.
+    pub fn max_chunks(&self) -> usize {
+        self.max_chunks
+    }
+
+    /// Returns an iterator over the elements that are still in the arena, from oldest to
+    /// newest.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        let chunks = self.list.len();
+        self.list.iter().enumerate().flat_map(move |(i, chunk)| {
+            let len = if i + 1 == chunks { self.back_len } else { N };
+            chunk[..len]
+                .iter()
+                .map(|slot| unsafe { slot.assume_init_ref() })
+        })
+    }
+
+    /// Returns an iterator over mutable references to the elements that are still in the
+    /// arena, from oldest to newest.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        let chunks = self.list.len();
+        let back_len = self.back_len;
+        self.list
+            .iter_mut()
+            .enumerate()
+            .flat_map(move |(i, chunk)| {
+                let len = if i + 1 == chunks { back_len } else { N };
+                chunk[..len]
+                    .iter_mut()
+                    .map(|slot| unsafe { slot.assume_init_mut() })
+            })
+    }
+}
+
+impl<const N: usize, T> Drop for RollingArena<N, T> {
+    fn drop(&mut self) {
+        let chunks = self.list.len();
+        for (i, chunk) in self.list.iter_mut().enumerate() {
+            let len = if i + 1 == chunks { self.back_len } else { N };
+            for slot in &mut chunk[..len] {
+                unsafe { slot.assume_init_drop() };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn evicts_oldest_chunk() {
+        let mut arena = RollingArena::<3, i32>::new(2);
+        assert!(arena.is_empty());
+        for i in 0..6 {
+            arena.push(i);
+        }
+        assert_eq!(arena.len(), 6);
+        let oldest_chunk = arena.iter().next().unwrap() as *const i32;
+
+        *arena.push(6) *= 10;
+        assert_eq!(arena.len(), 4);
+        assert!(arena.iter().copied().eq([3, 4, 5, 60]));
+        // The memory of the evicted chunk is reused.
+        assert_eq!(arena.iter().nth(3).unwrap() as *const i32, oldest_chunk);
+
+        arena.iter_mut().for_each(|x| *x += 1);
+        for i in 7..9 {
+            arena.push(i);
+        }
+        assert!(arena.iter().copied().eq([4, 5, 6, 61, 7, 8]));
+    }
+
+    #[test]
+    fn drops_evicted_elements() {
+        let drop_counter = Rc::new(Cell::new(0));
+
+        let mut arena = RollingArena::<2, Rc<Cell<i32>>>::new(1);
+        for _ in 0..5 {
+            arena.push(Rc::clone(&drop_counter));
+        }
+        assert_eq!(Rc::strong_count(&drop_counter), 2);
+        drop(arena);
+        assert_eq!(Rc::strong_count(&drop_counter), 1);
+    }
+
+    #[test]
+    fn large_chunks() {
+        // A chunk is larger than the stack of a test thread.
+        let mut arena = RollingArena::<{ 1 << 20 }, u64>::new(1);
+        assert_eq!(*arena.push(1), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn without_chunks() {
+        RollingArena::<2, i32>::new(0);
+    }
+}
@@ -0,0 +1,96 @@
+use core::fmt;
+use core::marker::PhantomData;
+
+use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::frozen::FrozenArena;
+use crate::{Allocator, Arena};
+
+// `Arena` doesn't implement `Serialize`, because that would read the elements through a
+// shared reference, like `Clone` would. Freeze the arena first, or serialize
+// `arena.iter()` with `Serializer::collect_seq`.
+
+/// Serializes the elements as a sequence in allocation order.
+impl<const N: usize, T: Serialize, A: Allocator> Serialize for FrozenArena<N, T, A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+/// Deserializes a sequence and allocates its elements in a new arena, without collecting
+/// them first.
+impl<'de, const N: usize, T, A> Deserialize<'de> for Arena<N, T, A>
+where
+    T: Deserialize<'de>,
+    A: Allocator + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(ArenaVisitor(PhantomData))
+    }
+}
+
+impl<'de, const N: usize, T, A> Deserialize<'de> for FrozenArena<N, T, A>
+where
+    T: Deserialize<'de>,
+    A: Allocator + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Arena::deserialize(deserializer).map(Arena::freeze)
+    }
+}
+
+struct ArenaVisitor<const N: usize, T, A: Allocator>(PhantomData<fn() -> Arena<N, T, A>>);
+
+impl<'de, const N: usize, T, A> Visitor<'de> for ArenaVisitor<N, T, A>
+where
+    T: Deserialize<'de>,
+    A: Allocator + Default,
+{
+    type Value = Arena<N, T, A>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a sequence")
+    }
+
+    fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<Self::Value, S::Error> {
+        let arena = Arena::default();
+        while let Some(elem) = seq.next_element()? {
+            arena.alloc(elem);
+        }
+        Ok(arena)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::frozen::FrozenArena;
+    use crate::Arena;
+
+    #[test]
+    fn round_trip() {
+        let arena = Arena::<3, String>::new();
+        for i in 0..7 {
+            arena.alloc(i.to_string());
+        }
+        let json = serde_json::to_string(&arena.freeze()).unwrap();
+        assert_eq!(json, r#"["0","1","2","3","4","5","6"]"#);
+
+        let mut arena: Arena<3, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(arena.len(), 7);
+        assert_eq!(arena.chunk_count(), 3);
+        assert!(arena
+            .iter()
+            .map(String::as_str)
+            .eq(["0", "1", "2", "3", "4", "5", "6"]));
+
+        let frozen: FrozenArena<2, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(frozen.get(6).map(String::as_str), Some("6"));
+    }
+
+    #[test]
+    fn deserialize_error() {
+        assert!(serde_json::from_str::<Arena<3, i32>>("[1, 2, \"3\"]").is_err());
+        assert!(serde_json::from_str::<Arena<3, i32>>("{}").is_err());
+    }
+}
@@ -0,0 +1,252 @@
+use std::cell::{Cell, UnsafeCell};
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+/// An arena that can be allocated from by multiple threads at the same time.
+///
+/// Within a chunk, slots are handed out by atomically bumping an index, so allocations
+/// from different threads don't block each other. A mutex is only taken to install a new
+/// chunk once the current one is full.
+///
+/// Like [`Arena`](crate::Arena), `SyncArena` is invariant in `T`:
+///
+/// ```compile_fail
+/// use linked_list_arena::sync::SyncArena;
+///
+/// fn shorten<'a>(arena: &'a SyncArena<4, &'static str>) -> &'a SyncArena<4, &'a str> {
+///     arena
+/// }
+/// ```
+pub struct SyncArena<const N: usize, T> {
+    chunks: ErasedChunks,
+    /// The arena owns its elements, and `Cell` makes it invariant in `T`.
+    _marker: PhantomData<Cell<T>>,
+}
+
+/// The chunk list of a [`SyncArena`], with a type that doesn't mention `T`.
+///
+/// See `ErasedInner` in the crate root for why the elements are dropped through a
+/// function pointer.
+struct ErasedChunks {
+    /// The newest chunk, or null if nothing has been allocated yet.
+    head: AtomicPtr<()>,
+    /// Taken while a new chunk is installed, so that only one thread allocates it.
+    install_lock: Mutex<()>,
+    drop_chunks: unsafe fn(&mut ErasedChunks),
+}
+
+struct Chunk<const N: usize, T> {
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    /// The index of the next free slot.
+    ///
+    /// Every thread that tries to allocate from the chunk increments it, so it can grow
+    /// beyond `N` once the chunk is full.
+    next_index: AtomicUsize,
+    /// The next (older) chunk.
+    next: *mut Chunk<N, T>,
+}
+
+// Elements are moved in on one thread and may be dropped on another. No thread can get a
+// reference to an element that was allocated by another thread through the arena.
+unsafe impl<const N: usize, T: Send> Send for SyncArena<N, T> {}
+unsafe impl<const N: usize, T: Send> Sync for SyncArena<N, T> {}
+
+impl<const N: usize, T> Chunk<N, T> {
+    /// Allocates a chunk directly on the heap, leaving the slots uninitialized.
+    fn new(next: *mut Self) -> *mut Self {
+        let chunk = Box::into_raw(Box::<Self>::new_uninit()).cast::<Self>();
+        unsafe {
+            ptr::addr_of_mut!((*chunk).next_index).write(AtomicUsize::new(0));
+            ptr::addr_of_mut!((*chunk).next).write(next);
+        }
+        chunk
+    }
+}
+
+impl<const N: usize, T> SyncArena<N, T> {
+    /// Creates a new arena.
+    /// This function does not allocate any memory.
+    ///
+    /// ```compile_fail
+    /// let arena = linked_list_arena::sync::SyncArena::<0, i32>::new();
+    /// ```
+    pub fn new() -> Self {
+        const { crate::check_chunk_size::<N, T>() };
+        SyncArena {
+            chunks: ErasedChunks {
+                head: AtomicPtr::new(ptr::null_mut()),
+                install_lock: Mutex::new(()),
+                drop_chunks: drop_chunks::<N, T>,
+            },
+            _marker: PhantomData,
+        }
+    }
+
+    /// Allocates a new element in the arena and returns a mutable reference to it.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc(&self, elem: T) -> &mut T {
+        loop {
+            // Pairs with the `Release` store that installed the chunk.
+            let head = self.head().load(Ordering::Acquire).cast::<Chunk<N, T>>();
+            if let Some(chunk) = unsafe { head.as_ref() } {
+                let index = chunk.next_index.fetch_add(1, Ordering::Relaxed);
+                if index < N {
+                    // No other thread got this index, so we have unique access to the slot.
+                    return unsafe { (*chunk.slots[index].get()).write(elem) };
+                }
+            }
+            self.push_chunk(head);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head().load(Ordering::Relaxed).is_null()
+    }
+
+    /// Installs a new chunk in front of `full`, unless another thread already did that.
+    fn push_chunk(&self, full: *mut Chunk<N, T>) {
+        // A panic while holding the lock can't leave the chunk list in a broken state.
+        let _guard = self
+            .chunks
+            .install_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        // The head is only changed while holding the lock.
+        if self.head().load(Ordering::Relaxed).cast() == full {
+            let new_head = Chunk::new(full);
+            self.head().store(new_head.cast(), Ordering::Release);
+        }
+    }
+
+    fn head(&self) -> &AtomicPtr<()> {
+        &self.chunks.head
+    }
+}
+
+impl<const N: usize, T> Default for SyncArena<N, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Drops all elements and frees all chunks of a `SyncArena<N, T>`.
+///
+/// # Safety
+///
+/// The chunks must have been created by a `SyncArena<N, T>`.
+unsafe fn drop_chunks<const N: usize, T>(chunks: &mut ErasedChunks) {
+    let mut chunk = chunks.head.get_mut().cast::<Chunk<N, T>>();
+    while !chunk.is_null() {
+        let mut boxed = Box::from_raw(chunk);
+        // Every index below `N` that was handed out belongs to an initialized slot.
+        let len = (*boxed.next_index.get_mut()).min(N);
+        for slot in &mut boxed.slots[..len] {
+            slot.get_mut().assume_init_drop();
+        }
+        chunk = boxed.next;
+    }
+}
+
+impl Drop for ErasedChunks {
+    fn drop(&mut self) {
+        unsafe { (self.drop_chunks)(self) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::SyncArena;
+
+    #[test]
+    fn alloc() {
+        let arena = SyncArena::<3, i32>::new();
+        assert!(arena.is_empty());
+        let elems: Vec<&mut i32> = (0..7).map(|i| arena.alloc(i)).collect();
+        assert!(!arena.is_empty());
+        assert!(elems.into_iter().map(|elem| *elem).eq(0..7));
+    }
+
+    #[test]
+    fn large_chunks() {
+        // A chunk is larger than the stack of a test thread.
+        let arena = SyncArena::<{ 1 << 20 }, u64>::new();
+        assert_eq!(*arena.alloc(1), 1);
+    }
+
+    #[test]
+    fn alloc_from_many_threads() {
+        const COUNT: usize = if cfg!(miri) { 20 } else { 1000 };
+
+        let arena = SyncArena::<8, (usize, usize)>::new();
+        std::thread::scope(|s| {
+            for thread in 0..4 {
+                let arena = &arena;
+                s.spawn(move || {
+                    let elems: Vec<_> = (0..COUNT).map(|i| arena.alloc((thread, i))).collect();
+                    assert!(elems
+                        .iter()
+                        .enumerate()
+                        .all(|(i, elem)| **elem == (thread, i)));
+                });
+            }
+        });
+    }
+
+    struct CycleParticipant<'a> {
+        other: Cell<Option<&'a CycleParticipant<'a>>>,
+    }
+
+    #[test]
+    fn cycle() {
+        let arena = SyncArena::<4, _>::new();
+        let a = arena.alloc(CycleParticipant {
+            other: Cell::new(None),
+        });
+        let b = arena.alloc(CycleParticipant {
+            other: Cell::new(None),
+        });
+        a.other.set(Some(b));
+        b.other.set(Some(a));
+    }
+
+    struct WithDrop(Arc<AtomicUsize>);
+
+    impl Drop for WithDrop {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn drop_arena() {
+        let drop_counter = Arc::new(AtomicUsize::new(0));
+
+        let arena = SyncArena::<3, WithDrop>::new();
+        std::thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    for _ in 0..5 {
+                        arena.alloc(WithDrop(Arc::clone(&drop_counter)));
+                    }
+                });
+            }
+        });
+        drop(arena);
+
+        assert_eq!(drop_counter.load(Ordering::SeqCst), 15);
+    }
+
+    #[test]
+    fn send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SyncArena<8, i32>>();
+        assert_send_sync::<SyncArena<8, String>>();
+    }
+}
@@ -0,0 +1,121 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use crate::Arena;
+
+/// An arena that stores a small tag next to every element.
+///
+/// The tags can be used to iterate over the elements of one category and to account for
+/// the memory used by each category.
+pub struct TaggedArena<const N: usize, T> {
+    arena: Arena<N, T>,
+    /// The tag of every element, in allocation order.
+    tags: RefCell<Vec<u32>>,
+    /// The number of elements with each tag.
+    counts: RefCell<BTreeMap<u32, usize>>,
+}
+
+impl<const N: usize, T> TaggedArena<N, T> {
+    /// Creates a new arena.
+    /// This function does not allocate any memory.
+    pub fn new() -> Self {
+        TaggedArena {
+            arena: Arena::new(),
+            tags: RefCell::new(Vec::new()),
+            counts: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Allocates a new element with the given tag and returns a mutable reference to it.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_tagged(&self, tag: u32, elem: T) -> &mut T {
+        self.tags.borrow_mut().push(tag);
+        *self.counts.borrow_mut().entry(tag).or_insert(0) += 1;
+        self.arena.alloc(elem)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arena.is_empty()
+    }
+
+    /// Returns the number of elements with the given tag.
+    pub fn tag_len(&self, tag: u32) -> usize {
+        self.counts.borrow().get(&tag).copied().unwrap_or(0)
+    }
+
+    /// Returns the number of bytes occupied by the elements with the given tag.
+    pub fn tag_bytes(&self, tag: u32) -> usize {
+        self.tag_len(tag) * core::mem::size_of::<T>()
+    }
+
+    /// Returns all tags that are in use together with the number of elements with that
+    /// tag, ordered by tag.
+    pub fn tag_counts(&self) -> Vec<(u32, usize)> {
+        self.counts.borrow().iter().map(|(&t, &c)| (t, c)).collect()
+    }
+
+    /// Returns an iterator over the elements with the given tag, in allocation order.
+    pub fn iter_tag(&mut self, tag: u32) -> impl Iterator<Item = &T> + '_ {
+        self.iter_tag_mut(tag).map(|elem| &*elem)
+    }
+
+    /// Returns an iterator over mutable references to the elements with the given tag, in
+    /// allocation order.
+    pub fn iter_tag_mut(&mut self, tag: u32) -> impl Iterator<Item = &mut T> + '_ {
+        let tags = self.tags.get_mut();
+        // Every element is visited at most once, so the mutable references don't alias.
+        self.arena
+            .iter_ptrs()
+            .zip(tags.iter())
+            .filter(move |&(_, &t)| t == tag)
+            .map(|(mut ptr, _)| unsafe { ptr.as_mut() })
+    }
+
+    /// Consumes the arena and destroys it, calling the destructor of all elements.
+    pub fn destroy(self) {
+        self.arena.destroy();
+    }
+}
+
+impl<const N: usize, T> Default for TaggedArena<N, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXPR: u32 = 0;
+    const STMT: u32 = 1;
+
+    #[test]
+    fn empty_arena() {
+        let mut arena = TaggedArena::<4, i32>::new();
+        assert!(arena.is_empty());
+        assert_eq!(arena.tag_len(EXPR), 0);
+        assert_eq!(arena.tag_counts(), []);
+        assert_eq!(arena.iter_tag(EXPR).count(), 0);
+    }
+
+    #[test]
+    fn iterate_by_tag() {
+        let mut arena = TaggedArena::<3, i64>::new();
+        for i in 0..10 {
+            arena.alloc_tagged(if i % 3 == 0 { STMT } else { EXPR }, i);
+        }
+        assert!(!arena.is_empty());
+        assert_eq!(arena.tag_len(STMT), 4);
+        assert_eq!(arena.tag_len(EXPR), 6);
+        assert_eq!(arena.tag_bytes(EXPR), 6 * 8);
+        assert_eq!(arena.tag_counts(), [(EXPR, 6), (STMT, 4)]);
+        assert!(arena.iter_tag(STMT).copied().eq([0, 3, 6, 9]));
+
+        arena.iter_tag_mut(EXPR).for_each(|x| *x = -*x);
+        assert!(arena.iter_tag(EXPR).copied().eq([-1, -2, -4, -5, -7, -8]));
+        assert!(arena.iter_tag(STMT).copied().eq([0, 3, 6, 9]));
+        arena.destroy();
+    }
+}